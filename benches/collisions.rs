@@ -0,0 +1,80 @@
+// compares checking every bullet against every target with the spatial hash broadphase.
+//
+// usage: cargo bench --bench collisions
+
+use bevy::prelude::*;
+use btll::collision::{SpatialHash, Target, DEFAULT_HIT_RADIUS};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const TARGETS: usize = 200;
+const ARENA_SIZE: f32 = 100.0;
+
+fn random_points(rng: &mut StdRng, count: usize) -> Vec<Vec2> {
+    (0..count)
+        .map(|_| {
+            Vec2::new(
+                rng.gen_range(0.0..ARENA_SIZE),
+                rng.gen_range(0.0..ARENA_SIZE),
+            )
+        })
+        .collect()
+}
+
+fn targets(rng: &mut StdRng) -> Vec<Target> {
+    random_points(rng, TARGETS)
+        .into_iter()
+        .enumerate()
+        .map(|(i, position)| Target {
+            entity: Entity::from_raw(i as u32),
+            position,
+            radius: DEFAULT_HIT_RADIUS,
+            cover: false,
+        })
+        .collect()
+}
+
+fn naive(bullets: &[Vec2], targets: &[Target]) -> usize {
+    bullets
+        .iter()
+        .filter(|bullet| targets.iter().any(|target| target.contains(**bullet)))
+        .count()
+}
+
+fn broadphase(spatial_hash: &mut SpatialHash, bullets: &[Vec2], targets: &[Target]) -> usize {
+    spatial_hash.clear();
+    for target in targets {
+        spatial_hash.insert(*target);
+    }
+    bullets
+        .iter()
+        .filter(|bullet| spatial_hash.closest(**bullet, |_| true).is_some())
+        .count()
+}
+
+fn collisions(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let targets = targets(&mut rng);
+    let mut spatial_hash = SpatialHash::default();
+
+    let mut group = c.benchmark_group("collisions");
+    for bullets in [100, 1_000, 10_000] {
+        let bullets = random_points(&mut rng, bullets);
+        group.bench_with_input(
+            BenchmarkId::new("naive", bullets.len()),
+            &bullets,
+            |b, bullets| b.iter(|| naive(black_box(bullets), black_box(&targets))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("spatial_hash", bullets.len()),
+            &bullets,
+            |b, bullets| {
+                b.iter(|| broadphase(&mut spatial_hash, black_box(bullets), black_box(&targets)))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, collisions);
+criterion_main!(benches);
@@ -0,0 +1,140 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields};
+
+/// Derives `SHandleLoad` by calling `shandle_load` on every field, so any
+/// `SHandle` nested inside (directly, or through `Vec`, `Option`, tuples,
+/// `HashMap` values or other derived types) gets loaded.
+///
+/// Fields can be left out with `#[shandle(skip)]`.
+#[proc_macro_derive(SHandleLoad, attributes(shandle))]
+pub fn derive_shandle_load(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    if let Data::Union(_) = &input.data {
+        return syn::Error::new_spanned(&input, "SHandleLoad cannot be derived for unions")
+            .to_compile_error()
+            .into();
+    }
+
+    let load = walk(&input.data, quote!(&mut), |binding| {
+        quote! {
+            crate::shandle::SHandleLoad::shandle_load(#binding, loader)?;
+        }
+    });
+    let paths = walk(&input.data, quote!(&), |binding| {
+        quote! {
+            crate::shandle::SHandleLoad::shandle_paths(#binding, paths);
+        }
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics crate::shandle::SHandleLoad for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn shandle_load(
+                &mut self,
+                loader: &mut crate::shandle::SHandleLoader,
+            ) -> ::std::result::Result<(), crate::content_error::ContentError> {
+                #load
+                Ok(())
+            }
+
+            #[allow(unused_variables)]
+            fn shandle_paths<'p>(&'p self, paths: &mut ::std::vec::Vec<&'p str>) {
+                #paths
+            }
+        }
+    }
+    .into()
+}
+
+/// Generates `visit` for every field of a struct or enum, `reference` being how `self` is borrowed
+fn walk(
+    data: &Data,
+    reference: TokenStream2,
+    visit: impl Fn(TokenStream2) -> TokenStream2,
+) -> TokenStream2 {
+    match data {
+        Data::Struct(data) => {
+            let visits = data
+                .fields
+                .iter()
+                .enumerate()
+                .filter(|(_, field)| !is_skipped(field))
+                .map(|(i, field)| match &field.ident {
+                    Some(ident) => visit(quote!(#reference self.#ident)),
+                    None => {
+                        let index = syn::Index::from(i);
+                        visit(quote!(#reference self.#index))
+                    }
+                });
+            quote! { #(#visits)* }
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let name = &variant.ident;
+                let (pattern, visits) = walk_variant(&variant.fields, &visit);
+                quote! { Self::#name #pattern => { #(#visits)* } }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => unreachable!(),
+    }
+}
+
+fn walk_variant(
+    fields: &Fields,
+    visit: &impl Fn(TokenStream2) -> TokenStream2,
+) -> (TokenStream2, Vec<TokenStream2>) {
+    match fields {
+        Fields::Named(named) => {
+            let idents: Vec<_> = named
+                .named
+                .iter()
+                .filter(|field| !is_skipped(field))
+                .map(|field| field.ident.clone().unwrap())
+                .collect();
+            let visits = idents.iter().map(|ident| visit(quote!(#ident))).collect();
+            (quote!({ #(#idents,)* .. }), visits)
+        }
+        Fields::Unnamed(unnamed) => {
+            let mut bindings = Vec::new();
+            let mut visits = Vec::new();
+            for (i, field) in unnamed.unnamed.iter().enumerate() {
+                if is_skipped(field) {
+                    bindings.push(quote!(_));
+                } else {
+                    let ident = Ident::new(&format!("__field{i}"), Span::call_site());
+                    visits.push(visit(quote!(#ident)));
+                    bindings.push(quote!(#ident));
+                }
+            }
+            (quote!(( #(#bindings),* )), visits)
+        }
+        Fields::Unit => (quote!(), Vec::new()),
+    }
+}
+
+fn is_skipped(field: &Field) -> bool {
+    field.attrs.iter().any(|attr| {
+        if !attr.path().is_ident("shandle") {
+            return false;
+        }
+        let mut skip = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+            }
+            Ok(())
+        });
+        skip
+    })
+}
@@ -54,15 +54,21 @@ impl BillboardSpriteBundle {
     }
 }
 
+/// Rotation of a billboard facing the camera of a player rotated by `player_rotation`
+pub fn billboard_rotation(player_rotation: Quat) -> Quat {
+    let angle = player_rotation.to_euler(EulerRot::XYZ).2;
+    Quat::from_euler(EulerRot::XYZ, PI / 2.0, angle, 0.0)
+}
+
 /// Align billboard sprites' y rotation so that they face the player camera
 /// TODO: subtract global transform's rotation to work with child objects
 pub fn rotate_billboard_sprites(
-    mut query: Query<(&mut Transform, &GlobalTransform), (With<BillboardSprite>, Without<Player>)>,
+    mut query: Query<&mut Transform, (With<BillboardSprite>, Without<Player>)>,
     player: Query<&Transform, With<Player>>,
 ) {
-    let angle = player.single().rotation.to_euler(EulerRot::XYZ).2;
-    for (mut transform, _global_transform) in query.iter_mut() {
-        transform.rotation = Quat::from_euler(EulerRot::XYZ, PI / 2.0, angle, 0.0);
+    let rotation = billboard_rotation(player.single().rotation);
+    for mut transform in query.iter_mut() {
+        transform.rotation = rotation;
     }
 }
 
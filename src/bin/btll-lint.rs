@@ -0,0 +1,308 @@
+// Headless content validator.
+// Parses every content file in the assets folder with the same types the game uses and
+// reports problems that would otherwise only show up at runtime.
+//
+// usage: btll-lint [assets folder]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use btll::{
+    bullet::BulletOptions,
+    content::ContentManifest,
+    content_error::ContentError,
+    enemy::{Ai, EnemyOptions},
+    items::item::Item,
+    shandle::{asset_path_string, deserialize_ron, resolve_path, serialize_ron, SHandleLoad},
+    template::{self, TemplateChain, ValueSpans},
+    validate::{Problem, Validate},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+fn main() -> ExitCode {
+    let root = PathBuf::from(std::env::args().nth(1).unwrap_or_else(|| "assets".into()));
+
+    let mut files = Vec::new();
+    if let Err(err) = collect_files(&root, &mut files) {
+        eprintln!("{}: error: {err}", root.display());
+        return ExitCode::FAILURE;
+    }
+    files.sort();
+
+    let errors: usize = files.iter().map(|file| lint_file(&root, file)).sum();
+
+    if errors > 0 {
+        println!("{errors} error(s) found");
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Checks a single file with the type of its extension, returning the amount of errors found
+fn lint_file(root: &Path, file: &Path) -> usize {
+    match file.extension().and_then(|ext| ext.to_str()) {
+        Some("enemy") => lint::<EnemyOptions>(root, file),
+        Some("item") => lint::<Item>(root, file),
+        Some("bullet") => lint::<BulletOptions>(root, file),
+        Some("ai") => lint::<Ai>(root, file),
+        Some("manifest") => lint::<ContentManifest>(root, file),
+        _ => 0,
+    }
+}
+
+/// Checks a single file, returning the amount of errors found
+fn lint<T: DeserializeOwned + Serialize + SHandleLoad + Validate>(
+    root: &Path,
+    file: &Path,
+) -> usize {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(err) => {
+            println!("{}: error: {err}", file.display());
+            return 1;
+        }
+    };
+
+    // paths are resolved relative to the asset folder like the asset server does
+    let asset_path = file.strip_prefix(root).unwrap_or(file);
+
+    let (asset, chain) = match parse::<T>(root, asset_path, &text) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            print_error(root, &err.in_file(vec![asset_path_string(asset_path)]));
+            return 1;
+        }
+    };
+
+    let mut problems = Vec::new();
+    asset.validate(&mut problems);
+    let mut paths = Vec::new();
+    asset.shandle_paths(&mut paths);
+    for path in paths {
+        match resolve_path(asset_path, path) {
+            Ok(resolved) => {
+                if !root.join(&resolved).is_file() {
+                    problems.push(Problem::new(
+                        format!("referenced file \"{resolved}\" does not exist"),
+                        format!("\"{path}\""),
+                    ));
+                }
+            }
+            Err(err) => problems.push(Problem::new(err.to_string(), format!("\"{path}\""))),
+        }
+    }
+
+    // writing the file back out shouldn't lose anything, or editors would break it
+    match round_trip::<T>(&asset, &text, chain.as_ref()) {
+        Ok(true) => {}
+        Ok(false) => problems.push(Problem::new(
+            "content is lost when writing this file back out",
+            "",
+        )),
+        Err(err) => problems.push(Problem::new(
+            format!("could not write this file back out: {err}"),
+            "",
+        )),
+    }
+
+    // only used to point at lines, the file already parsed fine
+    let spans = template::value_spans(&text).unwrap_or_default();
+    for problem in &problems {
+        println!(
+            "{}:{}: error: {}",
+            file.display(),
+            line_of(&text, &spans, problem),
+            problem.message
+        );
+    }
+    problems.len()
+}
+
+/// Deserializes the file, merged with the templates it extends if it extends any.
+///
+/// Also returns the chain of templates.
+fn parse<T: DeserializeOwned>(
+    root: &Path,
+    asset_path: &Path,
+    text: &str,
+) -> Result<(T, Option<TemplateChain>), ContentError> {
+    let Some(mut chain) = TemplateChain::new(asset_path, text.as_bytes())? else {
+        return Ok((deserialize_ron(text.as_bytes())?, None));
+    };
+    while let Some(parent) = chain.next_parent()? {
+        let bytes = fs::read(root.join(&parent))
+            .map_err(|err| ContentError::new(err.to_string()).in_file(vec![parent.clone()]))?;
+        chain.push_parent(parent, &bytes)?;
+    }
+    Ok((chain.deserialize()?, Some(chain)))
+}
+
+/// Serializes the asset again, checking the result can be read and still has everything in
+/// `text`. Files extending a template are written back out extending it.
+fn round_trip<T: DeserializeOwned + Serialize>(
+    asset: &T,
+    text: &str,
+    chain: Option<&TemplateChain>,
+) -> Result<bool, ContentError> {
+    let written = serialize_ron(asset)?;
+    deserialize_ron::<T>(written.as_bytes())?;
+    match chain {
+        Some(chain) => chain.preserved_by(&chain.unmerge(&written)?),
+        None => template::preserves(text, &written),
+    }
+}
+
+/// Prints an error as `file:line:col: error: message`, with files relative to the working
+/// directory
+fn print_error(root: &Path, err: &ContentError) {
+    let mut location = err
+        .chain
+        .iter()
+        .map(|file| root.join(file).display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ");
+    if let Some((line, col)) = err.position {
+        location += &format!(":{line}:{col}");
+    }
+    match &err.field {
+        Some(field) => println!("{location}: error: in `{field}`: {}", err.message),
+        None => println!("{location}: error: {}", err.message),
+    }
+}
+
+/// 1-based line of the first occurrence of the problem's needle inside the value at its field.
+///
+/// Falls back to the closest value around it that is in this file (fields can also come from
+/// a template), and to the start of that value if the needle isn't in it.
+fn line_of(text: &str, spans: &ValueSpans, problem: &Problem) -> usize {
+    let span = (0..=problem.field.len())
+        .rev()
+        .find_map(|len| {
+            spans
+                .iter()
+                .find(|(path, _)| path[..] == problem.field[..len])
+        })
+        .map_or(0..text.len(), |(_, span)| span.clone());
+    let index = text[span.clone()]
+        .find(&problem.needle)
+        .map_or(span.start, |index| span.start + index);
+    text[..index].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENEMY: &str = r#"EnemyOptions (
+    health: Health ( max: 10, current: 20, team: Enemy ),
+    ai: Ai (
+        phases: {
+            "Start": Phase (
+                behaviors: [ShootAtPlayer (bullet: Serialized("a.bullet"), interval: 0.0)],
+                transitions: [
+                    (HealthLessThan (0.5), "Start"),
+                    (HealthLessThan (0.2), "Missing"),
+                ],
+            ),
+        },
+        current: "Start",
+    ),
+    drop_table: DropTable (
+        drops: [
+            (Serialized("a.item"), 0.5),
+            (Serialized("a.item"), 2.0),
+        ],
+    ),
+    sprite: Serialized("a.png"),
+)"#;
+
+    fn line(problem: Problem) -> usize {
+        line_of(ENEMY, &template::value_spans(ENEMY).unwrap(), &problem)
+    }
+
+    #[test]
+    fn finds_the_needle_inside_the_field() {
+        // "Start" first shows up as a phase name, but the problem is about `current`
+        assert_eq!(
+            line(Problem::new("", "\"Start\"").at("ai").at("current")),
+            13
+        );
+        assert_eq!(
+            line(
+                Problem::new("", "\"Missing\"")
+                    .at("ai")
+                    .at("phases")
+                    .at("Start")
+                    .at("transitions")
+            ),
+            9
+        );
+        // the same path twice in the file
+        let drop = Problem::new("", "\"a.item\"").at("drop_table").at("drops");
+        assert_eq!(line(drop.at("1")), 18);
+    }
+
+    #[test]
+    fn validated_problems_point_at_their_value() {
+        let enemy: EnemyOptions = deserialize_ron(ENEMY.as_bytes()).unwrap();
+        let mut problems = Vec::new();
+        enemy.validate(&mut problems);
+        let lines: Vec<_> = problems.into_iter().map(line).collect();
+        assert_eq!(lines, [2, 6, 9, 18]);
+    }
+
+    #[test]
+    fn assets_round_trip() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let mut files = Vec::new();
+        collect_files(&root, &mut files).unwrap();
+        for file in files {
+            let round_trips = match file.extension().and_then(|ext| ext.to_str()) {
+                Some("enemy") => round_trips::<EnemyOptions>(&root, &file),
+                Some("item") => round_trips::<Item>(&root, &file),
+                Some("bullet") => round_trips::<BulletOptions>(&root, &file),
+                Some("ai") => round_trips::<Ai>(&root, &file),
+                Some("manifest") => round_trips::<ContentManifest>(&root, &file),
+                _ => continue,
+            };
+            assert!(round_trips, "{} doesn't round trip", file.display());
+        }
+    }
+
+    fn round_trips<T: DeserializeOwned + Serialize>(root: &Path, file: &Path) -> bool {
+        let text = fs::read_to_string(file).unwrap();
+        let asset_path = file.strip_prefix(root).unwrap();
+        let (asset, chain) = parse::<T>(root, asset_path, &text).unwrap();
+        if let Some(chain) = &chain {
+            // written back out, it still extends its template instead of inlining it
+            let written = chain.unmerge(&serialize_ron(&asset).unwrap()).unwrap();
+            assert!(written.contains("extends"));
+            assert!(written.len() < chain.finish().len());
+        }
+        round_trip(&asset, &text, chain.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn falls_back_to_the_closest_value() {
+        // not in this file, like a field only its template has
+        assert_eq!(line(Problem::new("", "").at("health").at("shield")), 2);
+        assert_eq!(line(Problem::new("", "Missing").at("on_death")), 9);
+        assert_eq!(line(Problem::new("", "nowhere")), 1);
+    }
+}
@@ -1,30 +1,63 @@
-use std::f32::consts::PI;
-
-use bevy::{
-    asset::{AssetLoader, LoadedAsset},
-    prelude::*,
-    reflect::TypeUuid,
+use std::{
+    f32::consts::{PI, TAU},
+    time::Duration,
 };
+
+use bevy::{prelude::*, reflect::TypeUuid, utils::HashSet};
 use bevy_inspector_egui::InspectorOptions;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    billboard_sprite::SPRITE8,
+    bullet_pool::{log_bullet_pool_stats, BulletPool, BulletPoolStats},
+    collision::{rebuild_spatial_hash, Cover, SpatialHash, Target},
+    damage::{DamageEvent, DamageKind},
+    health::Health,
+    loader,
+    shandle::{SHandle, SHandleLoad},
+    simulation::{Interpolated, SimulationSet},
+    status_effect::{validate_effects, Effect},
+    validate::{in_field, Problem, Validate},
+};
 
-use crate::{billboard_sprite::SPRITE8, health::Health, loader, shandle::SHandle};
+/// how close a returning boomerang bullet has to get to its shooter to be caught
+pub const BOOMERANG_CATCH_RADIUS: f32 = 0.5;
 
 pub struct BulletPlugin;
 impl Plugin for BulletPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(propagate_bullets)
-            .add_system(despawn_bullets)
-            .add_system(detect_collisions)
-            .add_asset::<BulletOptions>()
-            .register_type::<Bullet>()
-            .register_asset_reflect::<BulletOptions>()
-            .register_type::<BulletOptions>()
-            .init_asset_loader::<BulletOptionsLoader>();
+        app.add_systems(
+            // bullets that time out can still hit on their last tick
+            (
+                rebuild_spatial_hash,
+                propagate_bullets,
+                detect_collisions,
+                despawn_bullets,
+            )
+                .chain()
+                .in_set(SimulationSet::Bullets)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .init_resource::<SpatialHash>()
+        .init_resource::<BulletPool>()
+        .add_system(reload_bullets)
+        .add_system(log_bullet_pool_stats)
+        .add_asset::<BulletOptions>()
+        .register_type::<Bullet>()
+        .register_type::<BulletPool>()
+        .register_type::<BulletPoolStats>()
+        .register_type::<Cover>()
+        .register_asset_reflect::<BulletOptions>()
+        .register_type::<BulletOptions>()
+        .register_type::<ShotPattern>()
+        .register_type::<Motion>()
+        .init_asset_loader::<BulletOptionsLoader>();
     }
 }
 
-#[derive(Default, Clone, PartialEq, Reflect, FromReflect, Deserialize, Debug)]
+#[derive(
+    Default, Clone, PartialEq, Reflect, FromReflect, Deserialize, Serialize, Debug, SHandleLoad,
+)]
 pub enum Team {
     Player,
     #[default]
@@ -40,96 +73,332 @@ impl Default for BulletOptions {
             sprite: SHandle::Serialized("bullet.png".into()),
             team: Team::default(),
             diagonal_sprite: false,
+            pattern: ShotPattern::default(),
+            motion: Motion::default(),
+            pierce: false,
+            max_hits: None,
+            passes_cover: false,
+            armor_piercing: false,
+            effects: Vec::new(),
         }
     }
 }
 
+/// State of a single bullet, everything that's the same for all bullets fired with the same
+/// options is read from its [`BulletOptions`]
 #[derive(Component, InspectorOptions, Reflect, Debug)]
 pub struct Bullet {
-    pub damage: u32,
+    pub options: Handle<BulletOptions>,
+    /// whoever fired the bullet, for motion relative to them
+    pub shooter: Entity,
     pub speed: f32,
+    /// current heading, or the angle around the shooter when orbiting
     pub direction: f32,
-    pub team: Team,
     pub timer: Timer,
+    /// distance from the shooter when orbiting
+    pub orbit_radius: f32,
+    /// whether a boomerang bullet has turned around
+    pub returning: bool,
+    /// whether the bullet is flying, or waiting in the [`BulletPool`] to be fired again
+    pub active: bool,
 }
 
+/// Everything a bullet has hit, so piercing bullets only hit each target once
+#[derive(Component, Default, Debug)]
+pub struct HitSet(pub HashSet<Entity>);
+
 #[derive(Bundle)]
 pub struct BulletBundle {
     pub bullet: Bullet,
+    pub hit_set: HitSet,
+    pub interpolated: Interpolated,
     pub sprite_bundle: SpriteBundle,
 }
 
 impl BulletBundle {
+    /// `None` if the options' sprite isn't loaded
     pub fn new(
-        mut bullet_options: BulletOptions,
+        options: Handle<BulletOptions>,
+        bullet_options: &BulletOptions,
+        shooter: Entity,
         direction: f32,
         position: Vec2,
-        asset_server: &AssetServer,
-    ) -> Self {
-        let mut sprite_rotation = direction;
-        if bullet_options.diagonal_sprite {
-            sprite_rotation -= PI / 4.0;
-        }
-
-        Self {
+    ) -> Option<Self> {
+        let transform = Transform::from_translation(position.extend(0.5))
+            .with_rotation(bullet_options.sprite_rotation(direction));
+        Some(Self {
+            interpolated: Interpolated::new(&transform),
             sprite_bundle: SpriteBundle {
-                texture: bullet_options.sprite.unwrap(),
-                transform: Transform::from_translation(position.extend(0.5))
-                    .with_rotation(Quat::from_rotation_z(sprite_rotation)),
+                texture: bullet_options.sprite.handle().ok()?,
+                transform,
                 sprite: SPRITE8,
                 ..default()
             },
             bullet: Bullet {
-                damage: bullet_options.damage,
+                options,
+                shooter,
                 speed: bullet_options.speed,
-                team: bullet_options.team,
                 timer: Timer::from_seconds(bullet_options.lifetime, TimerMode::Once),
                 direction,
+                orbit_radius: 0.0,
+                returning: false,
+                active: true,
             },
+            hit_set: HitSet::default(),
+        })
+    }
+
+    /// All bullets of one volley of the options' [`ShotPattern`], `volley` being how many
+    /// volleys were fired before this one
+    pub fn volley(
+        options: Handle<BulletOptions>,
+        bullet_options: &BulletOptions,
+        shooter: Entity,
+        direction: f32,
+        volley: u32,
+        position: Vec2,
+    ) -> Vec<Self> {
+        bullet_options
+            .pattern
+            .directions(direction, volley)
+            .into_iter()
+            .filter_map(|direction| {
+                Self::new(
+                    options.clone(),
+                    bullet_options,
+                    shooter,
+                    direction,
+                    position,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Apply changes to modified bullet files to bullets that are already flying
+pub fn reload_bullets(
+    mut ev_asset: EventReader<AssetEvent<BulletOptions>>,
+    assets: Res<Assets<BulletOptions>>,
+    mut query: Query<(&mut Bullet, &mut Transform, &mut Handle<Image>)>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Modified { handle } = ev {
+            if let Some(options) = assets.get(handle) {
+                for (mut bullet, mut transform, mut texture) in &mut query {
+                    if &bullet.options != handle {
+                        continue;
+                    }
+                    bullet.speed = options.speed;
+                    bullet
+                        .timer
+                        .set_duration(Duration::from_secs_f32(options.lifetime));
+                    transform.rotation = options.sprite_rotation(bullet.direction);
+                    if let SHandle::Loaded {
+                        handle: sprite_handle,
+                        ..
+                    } = &options.sprite
+                    {
+                        *texture = sprite_handle.clone();
+                    }
+                }
+            }
         }
     }
 }
 
+/// Put bullets that ran out of time back in the pool
 pub fn despawn_bullets(
-    mut commands: Commands,
-    mut query: Query<(Entity, &mut Bullet)>,
-    time: Res<Time>,
+    mut pool: ResMut<BulletPool>,
+    mut query: Query<(Entity, &mut Bullet, &mut Visibility)>,
+    fixed_time: Res<FixedTime>,
 ) {
-    for (entity, mut bullet) in &mut query {
-        bullet.timer.tick(time.delta());
+    for (entity, mut bullet, mut visibility) in &mut query {
+        if !bullet.active {
+            continue;
+        }
+        bullet.timer.tick(fixed_time.period);
 
         if bullet.timer.finished() {
-            commands.entity(entity).despawn();
+            pool.release(entity, &mut bullet, &mut visibility);
         }
     }
 }
 
-pub fn propagate_bullets(mut query: Query<(&Bullet, &mut Transform)>, time: Res<Time>) {
-    for (bullet, mut transform) in &mut query {
-        let mut offset =
-            Vec2::new(bullet.direction.cos(), bullet.direction.sin()).extend(0.0) * bullet.speed;
+pub fn propagate_bullets(
+    mut pool: ResMut<BulletPool>,
+    mut query: Query<(Entity, &mut Bullet, &mut Transform, &mut Visibility)>,
+    shooters: Query<&Transform, Without<Bullet>>,
+    healths: Query<&Health>,
+    spatial_hash: Res<SpatialHash>,
+    assets: Res<Assets<BulletOptions>>,
+    fixed_time: Res<FixedTime>,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    for (entity, mut bullet, mut transform, mut visibility) in &mut query {
+        let bullet = &mut *bullet;
+        if !bullet.active {
+            continue;
+        }
+        let Some(options) = assets.get(&bullet.options) else {
+            continue;
+        };
+        let motion = &options.motion;
+        let position = transform.translation.truncate();
+        let shooter = shooters
+            .get(bullet.shooter)
+            .ok()
+            .map(|shooter| shooter.translation.truncate());
+
+        bullet.speed = (bullet.speed + motion.acceleration * delta)
+            .max(motion.min_speed)
+            .min(motion.max_speed.unwrap_or(f32::INFINITY));
+
+        let velocity = match (&motion.orbit, shooter) {
+            (Some(orbit), Some(center)) => {
+                // spiral out at the bullet's speed until reaching the radius
+                bullet.direction += orbit.angular_speed.to_radians() * delta;
+                bullet.orbit_radius =
+                    (bullet.orbit_radius + bullet.speed * delta).min(orbit.radius);
+                let target = center + Vec2::from_angle(bullet.direction) * bullet.orbit_radius;
+                (target - position) / delta.max(f32::EPSILON)
+            }
+            _ => {
+                if motion.boomerang && bullet.timer.percent() >= 0.5 {
+                    match shooter {
+                        // fast bullets are caught before they can fly past the shooter
+                        Some(center)
+                            if bullet.returning
+                                && center.distance(position)
+                                    <= BOOMERANG_CATCH_RADIUS.max(bullet.speed * delta) =>
+                        {
+                            pool.release(entity, bullet, &mut visibility);
+                            continue;
+                        }
+                        Some(center) => bullet.direction = angle_of(center - position),
+                        None if !bullet.returning => bullet.direction += PI,
+                        None => {}
+                    }
+                    bullet.returning = true;
+                } else if let Some(homing) = &motion.homing {
+                    let team = &options.team;
+                    let target = spatial_hash.nearest(position, homing.range, |target| {
+                        healths
+                            .get(target.entity)
+                            .is_ok_and(|health| &health.team != team)
+                    });
+                    if let Some(target) = target {
+                        let turn =
+                            wrap_angle(angle_of(target.position - position) - bullet.direction);
+                        let max_turn = homing.turn_rate.to_radians() * delta;
+                        bullet.direction += turn.clamp(-max_turn, max_turn);
+                    }
+                }
 
-        offset *= time.delta_seconds();
-        transform.translation += offset;
+                let heading = Vec2::from_angle(bullet.direction);
+                let mut velocity = heading * bullet.speed;
+                if let Some(wave) = &motion.wave {
+                    // moving along a sine wave sideways, this is its derivative
+                    let frequency = TAU * wave.frequency;
+                    let time = bullet.timer.elapsed_secs();
+                    velocity +=
+                        heading.perp() * wave.amplitude * frequency * (frequency * time).cos();
+                }
+                velocity
+            }
+        };
+
+        transform.translation += (velocity * delta).extend(0.0);
+        if velocity != Vec2::ZERO {
+            transform.rotation = options.sprite_rotation(angle_of(velocity));
+        }
     }
 }
 
+fn angle_of(vector: Vec2) -> f32 {
+    vector.y.atan2(vector.x)
+}
+
+/// Wraps an angle into `-PI..=PI`
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+/// Rotation of a bullet sprite facing `direction`
+fn sprite_rotation(direction: f32, diagonal_sprite: bool) -> Quat {
+    let mut sprite_rotation = direction;
+    if diagonal_sprite {
+        sprite_rotation -= PI / 4.0;
+    }
+    Quat::from_rotation_z(sprite_rotation)
+}
+
+/// Send a [`DamageEvent`] for the closest targets each bullet is touching, up to its `max_hits`
 pub fn detect_collisions(
-    mut commands: Commands,
-    bullet_query: Query<(&Bullet, &Transform, Entity)>,
-    mut health_query: Query<(&mut Health, &Transform)>,
+    mut pool: ResMut<BulletPool>,
+    spatial_hash: Res<SpatialHash>,
+    mut bullet_query: Query<(
+        &mut Bullet,
+        &mut HitSet,
+        &Transform,
+        &mut Visibility,
+        Entity,
+    )>,
+    health_query: Query<&Health>,
+    assets: Res<Assets<BulletOptions>>,
+    mut ev_damage: EventWriter<DamageEvent>,
 ) {
-    for (bullet, bullet_transform, bullet_entity) in &bullet_query {
-        for (mut health, health_transform) in &mut health_query {
-            if bullet_transform
-                .translation
-                .distance(health_transform.translation)
-                < 0.5
-                && bullet.team != health.team
-            {
-                commands.entity(bullet_entity).despawn();
-                health.inflict_damage(bullet.damage);
+    for (mut bullet, mut hit_set, bullet_transform, mut visibility, bullet_entity) in
+        &mut bullet_query
+    {
+        if !bullet.active {
+            continue;
+        }
+        let Some(options) = assets.get(&bullet.options) else {
+            continue;
+        };
+        let position = bullet_transform.translation.truncate();
+
+        if !options.passes_cover && spatial_hash.query(position).any(|target| target.cover) {
+            pool.release(bullet_entity, &mut bullet, &mut visibility);
+            continue;
+        }
+
+        // closest first, so a bullet that can only hit once hits what it reached first
+        let mut targets: Vec<&Target> = spatial_hash
+            .query(position)
+            .filter(|target| !target.cover && !hit_set.0.contains(&target.entity))
+            .filter(|target| {
+                health_query
+                    .get(target.entity)
+                    .is_ok_and(|health| health.team != options.team)
+            })
+            .collect();
+        targets.sort_by(|a, b| {
+            a.position
+                .distance_squared(position)
+                .total_cmp(&b.position.distance_squared(position))
+        });
+
+        let max_hits = options.max_hits();
+        for target in targets {
+            if hit_set.0.len() as u32 >= max_hits {
+                break;
             }
+            ev_damage.send(DamageEvent {
+                target: target.entity,
+                source: Some(bullet.shooter),
+                amount: options.damage,
+                damage_kind: if options.armor_piercing {
+                    DamageKind::ArmorPiercing
+                } else {
+                    DamageKind::Normal
+                },
+                effects: options.effects.clone(),
+            });
+            hit_set.0.insert(target.entity);
+        }
+        if hit_set.0.len() as u32 >= max_hits {
+            pool.release(bullet_entity, &mut bullet, &mut visibility);
         }
     }
 }
@@ -145,7 +414,17 @@ pub fn detect_collisions(
 //     }
 // }
 
-#[derive(InspectorOptions, Reflect, FromReflect, Deserialize, TypeUuid, Debug, Clone)]
+#[derive(
+    InspectorOptions,
+    Reflect,
+    FromReflect,
+    Deserialize,
+    Serialize,
+    TypeUuid,
+    Debug,
+    Clone,
+    SHandleLoad,
+)]
 #[reflect(Default)]
 #[uuid = "422f5440-c59a-11ed-afa1-0242ac120002"]
 pub struct BulletOptions {
@@ -155,6 +434,450 @@ pub struct BulletOptions {
     pub sprite: SHandle<Image>,
     pub diagonal_sprite: bool,
     pub team: Team,
+    #[serde(default)]
+    pub pattern: ShotPattern,
+    #[serde(default)]
+    pub motion: Motion,
+    /// fly through targets instead of stopping at the first one, hitting each only once
+    #[serde(default)]
+    pub pierce: bool,
+    /// how many targets a piercing bullet can hit, no limit if not set
+    #[serde(default)]
+    pub max_hits: Option<u32>,
+    /// fly through [`Cover`]
+    #[serde(default)]
+    pub passes_cover: bool,
+    /// ignore the defense of whatever gets hit
+    #[serde(default)]
+    pub armor_piercing: bool,
+    /// status effects applied to whatever gets hit, with their duration in seconds:
+    /// `effects: [(Slowed, 2.0), (Bleeding, 3.0)]`
+    #[serde(default)]
+    pub effects: Vec<(Effect, f32)>,
+}
+
+impl BulletOptions {
+    pub fn sprite_rotation(&self, direction: f32) -> Quat {
+        sprite_rotation(direction, self.diagonal_sprite)
+    }
+
+    /// How many targets the bullet can hit before it's gone
+    pub fn max_hits(&self) -> u32 {
+        if self.pierce {
+            self.max_hits.unwrap_or(u32::MAX)
+        } else {
+            1
+        }
+    }
+}
+
+impl Validate for BulletOptions {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        if self.pattern.num_projectiles == 0 {
+            problems.push(Problem::new(
+                "num_projectiles must be at least 1",
+                "num_projectiles",
+            ));
+        }
+        if self.max_hits.is_some() && !self.pierce {
+            problems.push(Problem::new(
+                "max_hits only applies to bullets with pierce",
+                "max_hits",
+            ));
+        }
+        if self.max_hits == Some(0) {
+            problems.push(Problem::new("max_hits must be at least 1", "max_hits"));
+        }
+        if let Some(max_speed) = self.motion.max_speed {
+            if max_speed < self.motion.min_speed {
+                problems.push(Problem::new(
+                    format!(
+                        "max_speed {max_speed} is less than min_speed {}",
+                        self.motion.min_speed
+                    ),
+                    "max_speed",
+                ));
+            }
+        }
+        in_field("effects", problems, |problems| {
+            validate_effects(&self.effects, problems)
+        });
+    }
+}
+
+/// How many bullets get fired at once and in which directions, all angles are in degrees.
+///
+/// Every offset fires a fan of `num_projectiles` bullets, `arc_gap` apart and centered on
+/// the offset, or spread evenly around a full circle with `ring`:
+/// ```ron
+/// // three bullets 15 degrees apart, forwards and backwards
+/// pattern: ShotPattern(num_projectiles: 3, arc_gap: 15.0, offsets: [0.0, 180.0]),
+/// // a ring of 12 bullets turning 10 degrees every volley
+/// pattern: ShotPattern(num_projectiles: 12, ring: true, spiral_step: 10.0),
+/// ```
+#[derive(Reflect, FromReflect, Deserialize, Serialize, Debug, Clone, PartialEq, SHandleLoad)]
+#[serde(default)]
+pub struct ShotPattern {
+    pub num_projectiles: u32,
+    pub arc_gap: f32,
+    /// no offsets fires a single fan straight ahead
+    pub offsets: Vec<f32>,
+    pub ring: bool,
+    /// how much the whole pattern turns every volley, for spirals
+    pub spiral_step: f32,
+}
+
+impl Default for ShotPattern {
+    fn default() -> Self {
+        Self {
+            num_projectiles: 1,
+            arc_gap: 0.0,
+            offsets: Vec::new(),
+            ring: false,
+            spiral_step: 0.0,
+        }
+    }
+}
+
+impl ShotPattern {
+    /// Directions of every bullet in a volley in radians, aimed at `direction`
+    pub fn directions(&self, direction: f32, volley: u32) -> Vec<f32> {
+        let count = self.num_projectiles.max(1);
+        let gap = if self.ring {
+            TAU / count as f32
+        } else {
+            self.arc_gap.to_radians()
+        };
+        // center fans on their offset, rings start at it
+        let first = if self.ring {
+            0.0
+        } else {
+            -gap * (count - 1) as f32 / 2.0
+        };
+        let rotation = direction + (self.spiral_step * volley as f32 % 360.0).to_radians();
+
+        let offsets = if self.offsets.is_empty() {
+            &[0.0][..]
+        } else {
+            &self.offsets
+        };
+        offsets
+            .iter()
+            .flat_map(|offset| {
+                (0..count).map(move |i| rotation + offset.to_radians() + first + gap * i as f32)
+            })
+            .collect()
+    }
 }
 
 loader!(BulletOptions, BulletOptionsLoader, &["bullet"]);
+
+/// Optional changes to how a bullet flies, on top of going straight at `speed`:
+/// ```ron
+/// motion: Motion(
+///     wave: Some(Wave(amplitude: 0.5, frequency: 2.0)),
+///     acceleration: 2.0,
+///     max_speed: Some(10.0),
+/// ),
+/// ```
+#[derive(
+    Reflect, FromReflect, Deserialize, Serialize, Debug, Clone, Default, PartialEq, SHandleLoad,
+)]
+#[serde(default)]
+pub struct Motion {
+    /// wobble sideways while flying
+    pub wave: Option<Wave>,
+    /// change in speed per second, negative to slow down
+    pub acceleration: f32,
+    pub min_speed: f32,
+    pub max_speed: Option<f32>,
+    /// fly back to the shooter after half of the lifetime
+    pub boomerang: bool,
+    /// circle around the shooter instead of flying away
+    pub orbit: Option<Orbit>,
+    /// steer towards the nearest target of another team
+    pub homing: Option<Homing>,
+}
+
+#[derive(Reflect, FromReflect, Deserialize, Serialize, Debug, Clone, PartialEq, SHandleLoad)]
+pub struct Wave {
+    /// how far to the side the bullet goes
+    pub amplitude: f32,
+    /// waves per second
+    pub frequency: f32,
+}
+
+#[derive(Reflect, FromReflect, Deserialize, Serialize, Debug, Clone, PartialEq, SHandleLoad)]
+pub struct Orbit {
+    pub radius: f32,
+    /// degrees per second, negative to go clockwise
+    pub angular_speed: f32,
+}
+
+#[derive(Reflect, FromReflect, Deserialize, Serialize, Debug, Clone, PartialEq, SHandleLoad)]
+pub struct Homing {
+    /// degrees per second
+    pub turn_rate: f32,
+    /// how far away targets are noticed
+    pub range: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{asset_app, MemoryAssetIo};
+
+    #[test]
+    fn shot_patterns_fire_in_the_right_directions() {
+        let pattern = |num_projectiles, arc_gap, offsets: &[f32], ring, spiral_step| ShotPattern {
+            num_projectiles,
+            arc_gap,
+            offsets: offsets.to_vec(),
+            ring,
+            spiral_step,
+        };
+        // (pattern, aimed at, volley, directions in degrees)
+        let cases = [
+            (ShotPattern::default(), 90.0, 0, vec![90.0]),
+            // fans are centered on where they're aimed
+            (
+                pattern(3, 15.0, &[], false, 0.0),
+                0.0,
+                0,
+                vec![-15.0, 0.0, 15.0],
+            ),
+            (pattern(2, 10.0, &[], false, 0.0), 90.0, 0, vec![85.0, 95.0]),
+            // a fan for every offset
+            (
+                pattern(2, 10.0, &[0.0, 180.0], false, 0.0),
+                0.0,
+                0,
+                vec![-5.0, 5.0, 175.0, 185.0],
+            ),
+            (
+                pattern(1, 0.0, &[-30.0, 30.0], false, 0.0),
+                0.0,
+                0,
+                vec![-30.0, 30.0],
+            ),
+            // rings are spread 360 / N apart, ignoring arc_gap
+            (
+                pattern(4, 15.0, &[], true, 0.0),
+                0.0,
+                0,
+                vec![0.0, 90.0, 180.0, 270.0],
+            ),
+            (
+                pattern(3, 0.0, &[], true, 0.0),
+                30.0,
+                0,
+                vec![30.0, 150.0, 270.0],
+            ),
+            // spirals turn by their step every volley
+            (pattern(2, 0.0, &[], true, 10.0), 0.0, 1, vec![10.0, 190.0]),
+            (pattern(2, 0.0, &[], true, 10.0), 0.0, 5, vec![50.0, 230.0]),
+            (pattern(1, 0.0, &[], false, 100.0), 0.0, 4, vec![40.0]),
+        ];
+        for (pattern, aim, volley, expected) in cases {
+            let directions: Vec<f32> = pattern
+                .directions(f32::to_radians(aim), volley)
+                .into_iter()
+                .map(f32::to_degrees)
+                .collect();
+            assert_eq!(directions.len(), expected.len(), "{pattern:?}");
+            for (direction, want) in directions.iter().zip(&expected) {
+                assert!(
+                    (direction - want).abs() < 1e-3,
+                    "{pattern:?} volley {volley}: {directions:?} != {expected:?}"
+                );
+            }
+        }
+    }
+
+    /// An app flying a bullet with `motion` from the origin towards +x, at 50 ticks per second,
+    /// returning the shooter and the bullet
+    fn flying(lifetime: f32, motion: Motion) -> (App, Entity, Entity) {
+        let mut app = asset_app(MemoryAssetIo::default());
+        app.add_asset::<BulletOptions>()
+            .init_resource::<BulletPool>()
+            .init_resource::<SpatialHash>()
+            .insert_resource(FixedTime::new_from_secs(0.02))
+            .add_systems((propagate_bullets, despawn_bullets).chain());
+
+        let options = BulletOptions {
+            speed: 5.0,
+            lifetime,
+            sprite: SHandle::Loaded {
+                path: "bullet.png".into(),
+                handle: Handle::default(),
+            },
+            motion,
+            ..default()
+        };
+        let shooter = app.world.spawn(TransformBundle::default()).id();
+        let bundle =
+            BulletBundle::new(Handle::default(), &options, shooter, 0.0, Vec2::ZERO).unwrap();
+        app.world
+            .resource_mut::<Assets<BulletOptions>>()
+            .set_untracked(Handle::<BulletOptions>::default(), options);
+        let bullet = app.world.spawn(bundle).id();
+        (app, shooter, bullet)
+    }
+
+    fn position(app: &App, entity: Entity) -> Vec2 {
+        app.world
+            .get::<Transform>(entity)
+            .unwrap()
+            .translation
+            .truncate()
+    }
+
+    fn ticks(app: &mut App, ticks: usize) {
+        for _ in 0..ticks {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn waves_wobble_sideways() {
+        let wave = Wave {
+            amplitude: 0.5,
+            frequency: 0.5,
+        };
+        let (mut app, _, bullet) = flying(
+            10.0,
+            Motion {
+                wave: Some(wave),
+                ..default()
+            },
+        );
+        // a quarter of a wave is all the way to the side, half of one is back in the middle
+        ticks(&mut app, 25);
+        let quarter = position(&app, bullet);
+        assert!((quarter.x - 2.5).abs() < 1e-3, "{quarter}");
+        assert!((quarter.y - 0.5).abs() < 0.05, "{quarter}");
+        ticks(&mut app, 25);
+        let half = position(&app, bullet);
+        assert!((half.x - 5.0).abs() < 1e-3, "{half}");
+        assert!(half.y.abs() < 0.05, "{half}");
+    }
+
+    #[test]
+    fn acceleration_stays_within_the_speed_limits() {
+        let speed_after = |acceleration, min_speed, max_speed| {
+            let (mut app, _, bullet) = flying(
+                10.0,
+                Motion {
+                    acceleration,
+                    min_speed,
+                    max_speed,
+                    ..default()
+                },
+            );
+            ticks(&mut app, 10);
+            app.world.get::<Bullet>(bullet).unwrap().speed
+        };
+        assert!((speed_after(10.0, 0.0, None) - 7.0).abs() < 1e-3);
+        assert_eq!(speed_after(100.0, 0.0, Some(8.0)), 8.0);
+        assert_eq!(speed_after(-100.0, 2.0, None), 2.0);
+    }
+
+    #[test]
+    fn orbits_circle_their_shooter() {
+        let orbit = Orbit {
+            radius: 2.0,
+            angular_speed: 90.0,
+        };
+        let (mut app, shooter, bullet) = flying(
+            10.0,
+            Motion {
+                orbit: Some(orbit),
+                ..default()
+            },
+        );
+        // spiralling out at 5 units per second reaches the radius in 20 ticks
+        ticks(&mut app, 10);
+        assert!((position(&app, bullet).length() - 1.0).abs() < 1e-3);
+        ticks(&mut app, 40);
+        let around = position(&app, bullet);
+        assert!((around.length() - 2.0).abs() < 1e-3, "{around}");
+        // a quarter turn per second, starting from +x
+        let angle = angle_of(around).to_degrees();
+        assert!((angle - 90.0).abs() < 1e-2, "{angle}");
+
+        // and follow them around
+        app.world.get_mut::<Transform>(shooter).unwrap().translation = Vec3::new(3.0, 1.0, 0.0);
+        ticks(&mut app, 1);
+        let center = Vec2::new(3.0, 1.0);
+        assert!((position(&app, bullet).distance(center) - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn homing_turns_towards_the_nearest_enemy_at_the_turn_rate() {
+        let homing = Homing {
+            turn_rate: 90.0,
+            range: 10.0,
+        };
+        let (mut app, _, bullet) = flying(
+            10.0,
+            Motion {
+                homing: Some(homing),
+                ..default()
+            },
+        );
+        // bullets are on the enemy team by default, the closer enemy isn't a target
+        let ally = app.world.spawn(Health::new(10, Team::Enemy)).id();
+        let target = app.world.spawn(Health::new(10, Team::Player)).id();
+        let mut spatial_hash = app.world.resource_mut::<SpatialHash>();
+        for (entity, position) in [(ally, Vec2::new(0.0, -2.0)), (target, Vec2::new(0.0, 5.0))] {
+            spatial_hash.insert(Target {
+                entity,
+                position,
+                radius: 0.5,
+                cover: false,
+            });
+        }
+
+        let max_turn = 90.0_f32.to_radians() * 0.02;
+        let mut direction = 0.0;
+        for _ in 0..20 {
+            ticks(&mut app, 1);
+            let turned = app.world.get::<Bullet>(bullet).unwrap().direction;
+            let wanted = angle_of(Vec2::new(0.0, 5.0) - position(&app, bullet));
+            assert!(turned > direction, "turned away from the target");
+            assert!(turned - direction <= max_turn + 1e-5, "turned too fast");
+            assert!(turned < wanted, "turned past the target");
+            direction = turned;
+        }
+        // still far from facing it, so it turned as fast as it could the whole time
+        assert!((direction - 20.0 * max_turn).abs() < 1e-4);
+    }
+
+    #[test]
+    fn boomerangs_are_caught_by_their_shooter() {
+        let (mut app, shooter, bullet) = flying(
+            1.0,
+            Motion {
+                boomerang: true,
+                ..default()
+            },
+        );
+
+        // out for half of the lifetime, then a bit of the way back
+        ticks(&mut app, 30);
+        let position = app.world.get::<Transform>(bullet).unwrap().translation;
+        assert!(app.world.get::<Bullet>(bullet).unwrap().returning);
+        assert!((position.x - 2.0).abs() < 0.01);
+
+        // the shooter walking up to it catches it long before it would time out
+        app.world.get_mut::<Transform>(shooter).unwrap().translation = position + Vec3::X * 0.3;
+        app.update();
+        let caught = app.world.get::<Bullet>(bullet).unwrap();
+        assert!(!caught.active);
+        assert!(!caught.timer.finished());
+        assert_eq!(
+            app.world.get::<Visibility>(bullet),
+            Some(&Visibility::Hidden)
+        );
+    }
+}
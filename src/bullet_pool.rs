@@ -0,0 +1,209 @@
+// reuses bullet entities instead of spawning and despawning one for every shot.
+//
+// a bullet that's done is hidden and marked inactive, keeping all its components, and the next
+// shot overwrites them. since the entity keeps the same components it never moves between
+// archetypes, which heavy bullet patterns would otherwise do thousands of times a second.
+
+use bevy::prelude::*;
+
+use crate::bullet::{Bullet, BulletBundle};
+
+/// seconds between logging the pool's stats
+pub const STATS_LOG_INTERVAL: f32 = 10.0;
+
+/// Bullet entities waiting to be fired again, and how many bullets can fly at once
+#[derive(Resource, Reflect, Debug)]
+#[reflect(Resource)]
+pub struct BulletPool {
+    /// most bullets that can be flying at once, shots past it are dropped
+    pub cap: usize,
+    free: Vec<Entity>,
+    stats: BulletPoolStats,
+}
+
+#[derive(Reflect, Default, Clone, Copy, Debug)]
+pub struct BulletPoolStats {
+    /// bullets flying right now
+    pub active: usize,
+    /// most bullets that were ever flying at once
+    pub peak_active: usize,
+    /// bullet entities waiting in the pool
+    pub pooled: usize,
+    /// bullet entities that had to be spawned because the pool was empty
+    pub spawned: u64,
+    /// shots that reused a pooled entity
+    pub reused: u64,
+    /// shots that were dropped because `cap` bullets were already flying
+    pub dropped: u64,
+    /// pooled entities that were despawned by something else, and dropped from the pool
+    pub stale: u64,
+}
+
+impl Default for BulletPool {
+    fn default() -> Self {
+        Self::new(4096)
+    }
+}
+
+impl BulletPool {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            cap,
+            free: Vec::new(),
+            stats: BulletPoolStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> BulletPoolStats {
+        self.stats
+    }
+
+    /// Fire a bullet, reusing a pooled entity if there is one. Returns `None` if the cap is
+    /// reached and the bullet was dropped.
+    pub fn spawn(&mut self, commands: &mut Commands, bundle: BulletBundle) -> Option<Entity> {
+        if self.stats.active >= self.cap {
+            self.stats.dropped += 1;
+            return None;
+        }
+        self.stats.active += 1;
+        self.stats.peak_active = self.stats.peak_active.max(self.stats.active);
+
+        while let Some(entity) = self.free.pop() {
+            self.stats.pooled = self.free.len();
+            let Some(mut pooled) = commands.get_entity(entity) else {
+                self.stats.stale += 1;
+                continue;
+            };
+            self.stats.reused += 1;
+            // the entity already has all of these, so this only overwrites them
+            pooled.insert(bundle);
+            return Some(entity);
+        }
+        self.stats.spawned += 1;
+        Some(commands.spawn(bundle).id())
+    }
+
+    pub fn spawn_batch(
+        &mut self,
+        commands: &mut Commands,
+        bundles: impl IntoIterator<Item = BulletBundle>,
+    ) {
+        for bundle in bundles {
+            self.spawn(commands, bundle);
+        }
+    }
+
+    /// Hide a bullet that's done and keep it around to be fired again
+    pub fn release(&mut self, entity: Entity, bullet: &mut Bullet, visibility: &mut Visibility) {
+        if !bullet.active {
+            return;
+        }
+        bullet.active = false;
+        *visibility = Visibility::Hidden;
+        self.stats.active = self.stats.active.saturating_sub(1);
+        self.free.push(entity);
+        self.stats.pooled = self.free.len();
+    }
+}
+
+/// Log the pool's stats every [`STATS_LOG_INTERVAL`] seconds, and warn when shots were dropped
+pub fn log_bullet_pool_stats(
+    pool: Res<BulletPool>,
+    time: Res<Time>,
+    mut since_logged: Local<f32>,
+    mut logged: Local<BulletPoolStats>,
+) {
+    *since_logged += time.delta_seconds();
+    if *since_logged < STATS_LOG_INTERVAL {
+        return;
+    }
+    *since_logged = 0.0;
+
+    let stats = pool.stats();
+    if stats.dropped > logged.dropped {
+        warn!(
+            "{} shots were dropped, {} bullets were already flying",
+            stats.dropped - logged.dropped,
+            pool.cap
+        );
+    }
+    debug!("bullet pool: {stats:?}");
+    *logged = stats;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+    use crate::{bullet::BulletOptions, shandle::SHandle};
+
+    fn bundle() -> BulletBundle {
+        let options = BulletOptions {
+            sprite: SHandle::Loaded {
+                path: "bullet.png".into(),
+                handle: Handle::default(),
+            },
+            ..default()
+        };
+        BulletBundle::new(
+            Handle::default(),
+            &options,
+            Entity::from_raw(0),
+            0.0,
+            Vec2::ZERO,
+        )
+        .unwrap()
+    }
+
+    fn spawn(world: &mut World, pool: &mut BulletPool) -> Option<Entity> {
+        let mut queue = CommandQueue::default();
+        let entity = pool.spawn(&mut Commands::new(&mut queue, world), bundle());
+        queue.apply(world);
+        entity
+    }
+
+    fn release(world: &mut World, pool: &mut BulletPool, entity: Entity) {
+        let mut query = world.query::<(&mut Bullet, &mut Visibility)>();
+        let (mut bullet, mut visibility) = query.get_mut(world, entity).unwrap();
+        pool.release(entity, &mut bullet, &mut visibility);
+    }
+
+    #[test]
+    fn bullets_are_reused() {
+        let mut world = World::new();
+        let mut pool = BulletPool::new(2);
+        let first = spawn(&mut world, &mut pool).unwrap();
+        release(&mut world, &mut pool, first);
+        assert_eq!(pool.stats().pooled, 1);
+        assert_eq!(world.get::<Visibility>(first), Some(&Visibility::Hidden));
+        assert!(!world.get::<Bullet>(first).unwrap().active);
+        assert_eq!(spawn(&mut world, &mut pool), Some(first));
+        // shown again, bullets have no parent so inheriting visibility makes them visible
+        assert_eq!(world.get::<Visibility>(first), Some(&Visibility::Inherited));
+        assert!(world.get::<Bullet>(first).unwrap().active);
+        spawn(&mut world, &mut pool).unwrap();
+        // the cap is reached
+        assert_eq!(spawn(&mut world, &mut pool), None);
+
+        let stats = pool.stats();
+        assert_eq!((stats.spawned, stats.reused, stats.dropped), (2, 1, 1));
+        assert_eq!((stats.active, stats.peak_active, stats.pooled), (2, 2, 0));
+    }
+
+    #[test]
+    fn despawned_bullets_are_dropped_from_the_pool() {
+        let mut world = World::new();
+        let mut pool = BulletPool::default();
+        let first = spawn(&mut world, &mut pool).unwrap();
+        release(&mut world, &mut pool, first);
+        world.despawn(first);
+
+        let second = spawn(&mut world, &mut pool).unwrap();
+        assert_ne!(second, first);
+        assert!(world.get::<Bullet>(second).is_some());
+        let stats = pool.stats();
+        assert_eq!((stats.spawned, stats.reused, stats.stale), (2, 0, 1));
+        assert_eq!(stats.pooled, 0);
+    }
+}
@@ -21,8 +21,8 @@ impl CameraProjection for DiagonalProjection {
         Mat4::orthographic_rh(
             -self.aspect * self.scale * SQRT_2,
             self.aspect * self.scale * SQRT_2,
-            -1.0 * self.scale,
-            1.0 * self.scale,
+            -self.scale,
+            self.scale,
             self.near,
             self.far,
         )
@@ -49,6 +49,33 @@ impl Default for DiagonalProjection {
     }
 }
 
+/// The point on the ground (`z = 0`) under the cursor.
+///
+/// `cursor` is the cursor position in a window of `window_size`, from the bottom left like
+/// [`Window::cursor_position`] returns it. Returns `None` if the camera is looking parallel
+/// to the ground.
+pub fn cursor_to_ground(
+    cursor: Vec2,
+    window_size: Vec2,
+    camera_transform: &GlobalTransform,
+    projection: &DiagonalProjection,
+) -> Option<Vec2> {
+    let ndc = cursor / window_size * 2.0 - Vec2::ONE;
+    let ndc_to_world =
+        camera_transform.compute_matrix() * projection.get_projection_matrix().inverse();
+
+    // the cursor is a ray going into the screen, find where it hits the ground
+    let near = ndc_to_world.project_point3(ndc.extend(0.0));
+    let far = ndc_to_world.project_point3(ndc.extend(1.0));
+    let direction = far - near;
+    // relative to the length of the ray, which is as long as the camera sees
+    if direction.z.abs() < f32::EPSILON * direction.length() {
+        return None;
+    }
+    let t = -near.z / direction.z;
+    Some((near + direction * t).truncate())
+}
+
 #[derive(Bundle)]
 pub struct DiagonalCameraBundle {
     pub camera: Camera,
@@ -138,3 +165,68 @@ impl Plugin for DiagonalProjectionPlugin {
             .add_system(unsort_y.in_base_set(CoreSet::First));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Vec2 = Vec2::new(800.0, 800.0);
+
+    fn ground(cursor: Vec2, camera: Transform) -> Option<Vec2> {
+        cursor_to_ground(
+            cursor,
+            WINDOW,
+            &GlobalTransform::from(camera),
+            &DiagonalProjection::default(),
+        )
+    }
+
+    fn camera(translation: Vec3) -> Transform {
+        DiagonalCameraBundle::default()
+            .transform
+            .with_translation(translation)
+    }
+
+    fn assert_near(a: Option<Vec2>, b: Vec2) {
+        let a = a.unwrap();
+        assert!(a.abs_diff_eq(b, 1e-4), "{a} != {b}");
+    }
+
+    #[test]
+    fn center_of_the_screen() {
+        assert_near(ground(WINDOW / 2.0, camera(Vec3::ZERO)), Vec2::ZERO);
+        assert_near(
+            ground(WINDOW / 2.0, camera(Vec3::new(3.0, -2.0, 0.0))),
+            Vec2::new(3.0, -2.0),
+        );
+        // looking down at 45 degrees, a camera above the ground sees further ahead
+        assert_near(
+            ground(WINDOW / 2.0, camera(Vec3::new(3.0, -2.0, 10.0))),
+            Vec2::new(3.0, 8.0),
+        );
+    }
+
+    #[test]
+    fn ground_is_stretched_the_same_both_ways() {
+        // half of the way from the center to the edges of the window
+        let offset = 5.0 * SQRT_2 / 2.0;
+        assert_near(
+            ground(WINDOW * 0.75, camera(Vec3::ZERO)),
+            Vec2::new(offset, offset),
+        );
+        assert_near(
+            ground(WINDOW * 0.25, camera(Vec3::ZERO)),
+            Vec2::new(-offset, -offset),
+        );
+        assert_near(
+            ground(Vec2::new(0.0, 400.0), camera(Vec3::ZERO)),
+            Vec2::new(-2.0 * offset, 0.0),
+        );
+    }
+
+    #[test]
+    fn looking_along_the_ground() {
+        let camera = Transform::from_rotation(Quat::from_rotation_x(PI / 2.0));
+        assert_eq!(ground(WINDOW / 2.0, camera), None);
+    }
+}
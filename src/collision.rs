@@ -0,0 +1,196 @@
+// broadphase for bullet collisions.
+//
+// every tick, all entities that can be hit (and cover that blocks bullets) are put into a
+// uniform grid, so a bullet only has to be checked against the targets in the cell it is in
+// instead of every target.
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::health::Health;
+
+pub const DEFAULT_HIT_RADIUS: f32 = 0.5;
+
+/// How close a bullet has to get to hit an entity, [`DEFAULT_HIT_RADIUS`] if missing
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+pub struct HitRadius(pub f32);
+
+impl Default for HitRadius {
+    fn default() -> Self {
+        Self(DEFAULT_HIT_RADIUS)
+    }
+}
+
+pub fn default_hit_radius() -> f32 {
+    DEFAULT_HIT_RADIUS
+}
+
+/// Walls and other obstacles that stop bullets, unless they have `passes_cover`
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+pub struct Cover;
+
+/// A target in the [`SpatialHash`]
+#[derive(Clone, Copy, Debug)]
+pub struct Target {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub radius: f32,
+    /// whether the target is [`Cover`] instead of something with health
+    pub cover: bool,
+}
+
+impl Target {
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.position.distance_squared(point) < self.radius * self.radius
+    }
+}
+
+/// Uniform grid of hittable entities, rebuilt every tick by [`rebuild_spatial_hash`].
+///
+/// Targets are put in every cell their hit radius overlaps, so finding what a point hits
+/// only needs to look at a single cell.
+#[derive(Resource)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<Target>>,
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(2.0)
+    }
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+        }
+    }
+
+    /// Remove all targets, keeping the cells that were used around for the next tick.
+    ///
+    /// Cells that were already empty are dropped, so the map doesn't grow with every cell
+    /// anything has ever been in.
+    pub fn clear(&mut self) {
+        self.cells.retain(|_, targets| !targets.is_empty());
+        for targets in self.cells.values_mut() {
+            targets.clear();
+        }
+    }
+
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    pub fn insert(&mut self, target: Target) {
+        let min = self.cell(target.position - Vec2::splat(target.radius));
+        let max = self.cell(target.position + Vec2::splat(target.radius));
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(target);
+            }
+        }
+    }
+
+    /// All targets whose hit radius contains `point`
+    pub fn query(&self, point: Vec2) -> impl Iterator<Item = &Target> {
+        self.cells
+            .get(&self.cell(point))
+            .into_iter()
+            .flatten()
+            .filter(move |target| target.contains(point))
+    }
+
+    /// The target closest to `point` whose hit radius contains it, if any
+    pub fn closest(&self, point: Vec2, mut filter: impl FnMut(&Target) -> bool) -> Option<&Target> {
+        self.query(point)
+            .filter(|target| filter(target))
+            .min_by(|a, b| {
+                a.position
+                    .distance_squared(point)
+                    .total_cmp(&b.position.distance_squared(point))
+            })
+    }
+
+    /// The closest target within `range` of `point`, whether it's touching it or not
+    pub fn nearest(
+        &self,
+        point: Vec2,
+        range: f32,
+        mut filter: impl FnMut(&Target) -> bool,
+    ) -> Option<&Target> {
+        let min = self.cell(point - Vec2::splat(range));
+        let max = self.cell(point + Vec2::splat(range));
+        (min.x..=max.x)
+            .flat_map(|x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(|target| target.position.distance_squared(point) <= range * range)
+            .filter(|target| filter(target))
+            .min_by(|a, b| {
+                a.position
+                    .distance_squared(point)
+                    .total_cmp(&b.position.distance_squared(point))
+            })
+    }
+}
+
+/// Anything that can be put in the [`SpatialHash`]
+type Hittable = Or<(With<Health>, With<Cover>)>;
+type HittableComponents<'a> = (
+    Entity,
+    &'a Transform,
+    Option<&'a HitRadius>,
+    Option<&'a Cover>,
+    Option<&'a Health>,
+);
+
+pub fn rebuild_spatial_hash(
+    mut spatial_hash: ResMut<SpatialHash>,
+    query: Query<HittableComponents, Hittable>,
+) {
+    spatial_hash.clear();
+    for (entity, transform, radius, cover, health) in &query {
+        // the dead can't be hit
+        if health.is_some_and(Health::is_dead) {
+            continue;
+        }
+        spatial_hash.insert(Target {
+            entity,
+            position: transform.translation.truncate(),
+            radius: radius.copied().unwrap_or_default().0,
+            cover: cover.is_some(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(position: Vec2) -> Target {
+        Target {
+            entity: Entity::from_raw(0),
+            position,
+            radius: 0.5,
+            cover: false,
+        }
+    }
+
+    #[test]
+    fn clear_drops_unused_cells() {
+        let mut hash = SpatialHash::new(2.0);
+        // a target moving across the map, one cell per tick
+        for x in 0..100 {
+            hash.clear();
+            hash.insert(target(Vec2::new(x as f32 * 2.0 + 1.0, 1.0)));
+            assert_eq!(hash.query(Vec2::new(x as f32 * 2.0 + 1.0, 1.0)).count(), 1);
+        }
+        // the cell of the last two ticks
+        assert_eq!(hash.cells.len(), 2);
+        hash.clear();
+        hash.clear();
+        assert!(hash.cells.is_empty());
+    }
+}
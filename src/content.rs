@@ -0,0 +1,260 @@
+use bevy::{
+    asset::{Asset, HandleId},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Debug};
+
+use crate::{
+    bullet::BulletOptions,
+    enemy::EnemyOptions,
+    items::item::Item,
+    loader,
+    shandle::{SHandle, SHandleLoad},
+    validate::Validate,
+};
+
+pub struct ContentPlugin;
+
+impl Plugin for ContentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ContentManifest>()
+            .init_asset_loader::<ContentManifestLoader>()
+            .init_resource::<ContentRegistry>()
+            .add_system(build_content_registry);
+    }
+}
+
+/// Lists all game content under stable ids, so it can be named independently of file paths.
+///
+/// An id is made of the content kind and the name given in the manifest, e.g. `item:test_weapon`.
+#[derive(Deserialize, Serialize, TypeUuid, Reflect, FromReflect, Debug, SHandleLoad)]
+#[uuid = "6a0f5c62-3c1e-4a7e-9d4b-2f8e1b7c9a01"]
+pub struct ContentManifest {
+    #[serde(default)]
+    pub items: HashMap<String, SHandle<Item>>,
+    #[serde(default)]
+    pub enemies: HashMap<String, SHandle<EnemyOptions>>,
+    #[serde(default)]
+    pub bullets: HashMap<String, SHandle<BulletOptions>>,
+}
+
+impl Validate for ContentManifest {}
+
+loader!(ContentManifest, ContentManifestLoader, &["manifest"]);
+
+/// A kind of content that can be registered in the [`ContentRegistry`]
+pub trait Content: Asset {
+    /// prefix of ids of this kind of content
+    const KIND: &'static str;
+}
+
+impl Content for Item {
+    const KIND: &'static str = "item";
+}
+
+impl Content for EnemyOptions {
+    const KIND: &'static str = "enemy";
+}
+
+impl Content for BulletOptions {
+    const KIND: &'static str = "bullet";
+}
+
+/// Maps stable content ids to loaded handles and back
+#[derive(Resource)]
+pub struct ContentRegistry {
+    manifest: Handle<ContentManifest>,
+    handles: HashMap<String, HandleUntyped>,
+    ids: HashMap<HandleId, String>,
+}
+
+impl FromWorld for ContentRegistry {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            manifest: world.resource::<AssetServer>().load("content.manifest"),
+            handles: HashMap::new(),
+            ids: HashMap::new(),
+        }
+    }
+}
+
+impl ContentRegistry {
+    /// Get the handle of the content with the given id, e.g. `item:test_weapon`
+    pub fn get<T: Content>(&self, id: &str) -> Option<Handle<T>> {
+        let (kind, _name) = id.split_once(':')?;
+        if kind != T::KIND {
+            return None;
+        }
+        self.handles
+            .get(id)
+            .map(|handle| handle.clone().typed::<T>())
+    }
+
+    /// Get the id the content behind the given handle is registered under
+    pub fn id<T: Content>(&self, handle: &Handle<T>) -> Option<&str> {
+        self.ids.get(&handle.id()).map(String::as_str)
+    }
+
+    fn clear(&mut self) {
+        self.handles.clear();
+        self.ids.clear();
+    }
+
+    fn register<T: Content + Reflect + FromReflect + Debug>(
+        &mut self,
+        entries: &HashMap<String, SHandle<T>>,
+    ) {
+        for (name, shandle) in entries {
+            if let SHandle::Loaded { handle, .. } = shandle {
+                let id = format!("{}:{name}", T::KIND);
+                self.ids.insert(handle.id(), id.clone());
+                self.handles.insert(id, handle.clone_untyped());
+            }
+        }
+    }
+}
+
+pub fn build_content_registry(
+    mut ev_asset: EventReader<AssetEvent<ContentManifest>>,
+    manifests: Res<Assets<ContentManifest>>,
+    mut registry: ResMut<ContentRegistry>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = ev {
+            if handle != &registry.manifest {
+                continue;
+            }
+            if let Some(manifest) = manifests.get(handle) {
+                registry.clear();
+                registry.register(&manifest.items);
+                registry.register(&manifest.enemies);
+                registry.register(&manifest.bullets);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{asset_app, MemoryAssetIo};
+
+    fn app() -> App {
+        let mut app = asset_app(MemoryAssetIo::default());
+        app.add_asset::<ContentManifest>()
+            .add_asset::<Item>()
+            .add_asset::<EnemyOptions>()
+            .add_asset::<BulletOptions>()
+            .init_resource::<ContentRegistry>()
+            .add_system(build_content_registry);
+        app
+    }
+
+    fn loaded<T: Content + Reflect + FromReflect + Debug>(
+        app: &App,
+        path: &str,
+    ) -> (SHandle<T>, Handle<T>) {
+        let handle = app.world.resource::<AssetServer>().get_handle(path);
+        let shandle = SHandle::Loaded {
+            path: path.into(),
+            handle: handle.clone(),
+        };
+        (shandle, handle)
+    }
+
+    /// Put `manifest` where the registry loads its manifest from
+    fn set_manifest(app: &mut App, manifest: ContentManifest) {
+        let handle = app.world.resource::<ContentRegistry>().manifest.clone();
+        app.world
+            .resource_mut::<Assets<ContentManifest>>()
+            .set_untracked(handle, manifest);
+        // asset events are sent at the end of a frame, and read in the next one
+        app.update();
+        app.update();
+    }
+
+    #[test]
+    fn ids_and_handles_map_both_ways() {
+        let mut app = app();
+        let (sword, sword_handle) = loaded::<Item>(&app, "sword.item");
+        let (goblin, goblin_handle) = loaded::<EnemyOptions>(&app, "goblin.enemy");
+        set_manifest(
+            &mut app,
+            ContentManifest {
+                items: HashMap::from([("sword".into(), sword)]),
+                enemies: HashMap::from([("goblin".into(), goblin)]),
+                bullets: HashMap::new(),
+            },
+        );
+
+        let registry = app.world.resource::<ContentRegistry>();
+        assert_eq!(
+            registry.get::<Item>("item:sword"),
+            Some(sword_handle.clone())
+        );
+        assert_eq!(
+            registry.get::<EnemyOptions>("enemy:goblin"),
+            Some(goblin_handle.clone())
+        );
+        assert_eq!(registry.id(&sword_handle), Some("item:sword"));
+        assert_eq!(registry.id(&goblin_handle), Some("enemy:goblin"));
+
+        // ids of another kind of content, or that aren't ids at all
+        assert_eq!(registry.get::<EnemyOptions>("item:sword"), None);
+        assert_eq!(registry.get::<Item>("enemy:goblin"), None);
+        assert_eq!(registry.get::<Item>("item:axe"), None);
+        assert_eq!(registry.get::<Item>("sword"), None);
+        let (_, unlisted) = loaded::<Item>(&app, "axe.item");
+        assert_eq!(registry.id(&unlisted), None);
+    }
+
+    #[test]
+    fn the_registry_follows_the_manifest() {
+        let mut app = app();
+        let (sword, sword_handle) = loaded::<Item>(&app, "sword.item");
+        let (axe, axe_handle) = loaded::<Item>(&app, "axe.item");
+        set_manifest(
+            &mut app,
+            ContentManifest {
+                items: HashMap::from([("sword".into(), sword.clone())]),
+                enemies: HashMap::new(),
+                bullets: HashMap::new(),
+            },
+        );
+        assert!(app
+            .world
+            .resource::<ContentRegistry>()
+            .get::<Item>("item:sword")
+            .is_some());
+
+        // the sword being renamed and an axe being added
+        set_manifest(
+            &mut app,
+            ContentManifest {
+                items: HashMap::from([("old_sword".into(), sword), ("axe".into(), axe)]),
+                enemies: HashMap::new(),
+                bullets: HashMap::new(),
+            },
+        );
+        let registry = app.world.resource::<ContentRegistry>();
+        assert_eq!(registry.get::<Item>("item:sword"), None);
+        assert_eq!(registry.id(&sword_handle), Some("item:old_sword"));
+        assert_eq!(registry.get::<Item>("item:axe"), Some(axe_handle.clone()));
+        assert_eq!(registry.id(&axe_handle), Some("item:axe"));
+
+        // other manifests are left alone
+        app.world
+            .resource_mut::<Assets<ContentManifest>>()
+            .add(ContentManifest {
+                items: HashMap::new(),
+                enemies: HashMap::new(),
+                bullets: HashMap::new(),
+            });
+        app.update();
+        app.update();
+        let registry = app.world.resource::<ContentRegistry>();
+        assert_eq!(registry.id(&axe_handle), Some("item:axe"));
+    }
+}
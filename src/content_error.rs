@@ -0,0 +1,337 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    asset::{AssetPath, LoadState},
+    prelude::*,
+};
+
+use crate::font::GameFont;
+
+pub struct ContentErrorPlugin;
+
+impl Plugin for ContentErrorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContentReporter>()
+            .init_resource::<GameFont>()
+            .init_resource::<ContentErrors>()
+            .add_startup_system(spawn_content_error_overlay)
+            .add_system(collect_content_errors)
+            .add_system(check_content_dependencies.after(collect_content_errors))
+            .add_system(update_content_error_overlay.after(collect_content_errors));
+    }
+}
+
+/// An error in a content file, with enough context to find where it came from
+#[derive(Debug, Clone)]
+pub struct ContentError {
+    /// files leading to the one the error is in, e.g. `test.enemy -> bullet.bullet -> bullet.png`
+    pub chain: Vec<String>,
+    /// line and column in the file
+    pub position: Option<(usize, usize)>,
+    /// path of the field the error is in, e.g. `ai.phases.Start.behaviors[1].interval`
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ContentError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            chain: Vec::new(),
+            position: None,
+            field: None,
+            message: message.into(),
+        }
+    }
+
+    /// The file the error is in
+    pub fn file(&self) -> Option<&str> {
+        self.chain.last().map(String::as_str)
+    }
+
+    /// Put the error in context of the file it happened in, `chain` leading up to that file.
+    ///
+    /// Errors that already know which files they happened in (like errors in templates)
+    /// are put after the file.
+    pub fn in_file(mut self, mut chain: Vec<String>) -> Self {
+        if self.chain.first() == chain.last() {
+            chain.pop();
+        }
+        chain.append(&mut self.chain);
+        self.chain = chain;
+        self
+    }
+}
+
+impl From<ron::error::SpannedError> for ContentError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        Self {
+            position: Some((err.position.line, err.position.col)),
+            ..Self::new(err.code.to_string())
+        }
+    }
+}
+
+impl From<ron::Error> for ContentError {
+    fn from(err: ron::Error) -> Self {
+        Self::new(err.to_string())
+    }
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.chain.join(" -> "))?;
+        if let Some((line, col)) = self.position {
+            write!(f, ":{line}:{col}")?;
+        }
+        if !self.chain.is_empty() || self.position.is_some() {
+            write!(f, ": ")?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, "in `{field}`: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ContentError {}
+
+/// Shared between the asset loaders, which run on other threads, and the app.
+///
+/// Keeps track of which files reference which, so errors can be shown with the chain of
+/// files that led to them, and queues up errors for [`collect_content_errors`].
+#[derive(Resource, Clone, Default)]
+pub struct ContentReporter(Arc<Mutex<ReporterState>>);
+
+#[derive(Default)]
+struct ReporterState {
+    /// the files referencing each file
+    referenced_by: HashMap<String, Vec<String>>,
+    /// referenced files that haven't finished loading yet
+    pending: Vec<String>,
+    /// new errors, or `None` for files that loaded fine
+    reports: Vec<(String, Option<ContentError>)>,
+}
+
+impl ContentReporter {
+    /// Record that the file `from` references the file `to`
+    pub fn reference(&self, from: &str, to: &str) {
+        let mut state = self.0.lock().unwrap();
+        let referenced_by = state.referenced_by.entry(to.to_string()).or_default();
+        if !referenced_by.iter().any(|file| file == from) {
+            referenced_by.push(from.to_string());
+        }
+        if !state.pending.iter().any(|file| file == to) {
+            state.pending.push(to.to_string());
+        }
+    }
+
+    /// The chain of files leading to `file`, ending with `file` itself
+    pub fn chain(&self, file: &str) -> Vec<String> {
+        let state = self.0.lock().unwrap();
+        let mut chain = vec![file.to_string()];
+        while let Some(parent) = state
+            .referenced_by
+            .get(chain.last().unwrap())
+            .and_then(|files| files.first())
+        {
+            if chain.contains(parent) {
+                break;
+            }
+            chain.push(parent.clone());
+        }
+        chain.reverse();
+        chain
+    }
+
+    pub fn error(&self, error: ContentError) {
+        let file = error.file().unwrap_or_default().to_string();
+        self.0.lock().unwrap().reports.push((file, Some(error)));
+    }
+
+    pub fn loaded(&self, file: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .reports
+            .push((file.to_string(), None));
+    }
+}
+
+/// Errors currently present in content files, by file
+#[derive(Resource, Default)]
+pub struct ContentErrors(pub BTreeMap<String, ContentError>);
+
+pub fn collect_content_errors(reporter: Res<ContentReporter>, mut errors: ResMut<ContentErrors>) {
+    let reports = std::mem::take(&mut reporter.0.lock().unwrap().reports);
+    for (file, report) in reports {
+        match report {
+            Some(error) => {
+                error!("{error}");
+                errors.0.insert(file, error);
+            }
+            None => {
+                if errors.0.contains_key(&file) {
+                    errors.0.remove(&file);
+                }
+            }
+        }
+    }
+}
+
+/// Report referenced files that failed to load, which also catches files loaded by
+/// bevy's own loaders like images
+pub fn check_content_dependencies(
+    reporter: Res<ContentReporter>,
+    errors: Res<ContentErrors>,
+    asset_server: Res<AssetServer>,
+) {
+    let pending = std::mem::take(&mut reporter.0.lock().unwrap().pending);
+    let mut still_pending = Vec::new();
+
+    for file in pending {
+        match asset_server.get_load_state(AssetPath::from(file.as_str())) {
+            LoadState::Loaded => reporter.loaded(&file),
+            // files loaded by our own loaders already reported a more detailed error
+            LoadState::Failed if errors.0.contains_key(&file) => {}
+            LoadState::Failed => reporter.error(
+                ContentError::new("failed to load (missing file or unsupported format)")
+                    .in_file(reporter.chain(&file)),
+            ),
+            _ => still_pending.push(file),
+        }
+    }
+
+    reporter.0.lock().unwrap().pending.extend(still_pending);
+}
+
+#[derive(Component)]
+pub struct ContentErrorOverlay;
+
+fn spawn_content_error_overlay(mut commands: Commands, font: Res<GameFont>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font.0.clone(),
+                font_size: 16.0,
+                color: Color::RED,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            ..default()
+        }),
+        ContentErrorOverlay,
+        Name::new("Content Error Overlay"),
+    ));
+}
+
+fn update_content_error_overlay(
+    errors: Res<ContentErrors>,
+    mut query: Query<&mut Text, With<ContentErrorOverlay>>,
+) {
+    if !errors.is_changed() {
+        return;
+    }
+    let text = errors
+        .0
+        .values()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    for mut overlay in &mut query {
+        overlay.sections[0].value = text.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_show_the_chain_position_and_field() {
+        let reporter = ContentReporter::default();
+        reporter.reference("test.enemy", "bullet.bullet");
+        reporter.reference("bullet.bullet", "bullet.png");
+        assert_eq!(
+            reporter.chain("bullet.png"),
+            ["test.enemy", "bullet.bullet", "bullet.png"]
+        );
+
+        let error = ContentError {
+            position: Some((3, 14)),
+            field: Some("motion.max_speed".into()),
+            ..ContentError::new("expected a float")
+        }
+        .in_file(reporter.chain("bullet.bullet"));
+        assert_eq!(error.file(), Some("bullet.bullet"));
+        assert_eq!(
+            error.to_string(),
+            "test.enemy -> bullet.bullet:3:14: in `motion.max_speed`: expected a float"
+        );
+
+        let missing = ContentError::new("failed to load").in_file(reporter.chain("bullet.png"));
+        assert_eq!(
+            missing.to_string(),
+            "test.enemy -> bullet.bullet -> bullet.png: failed to load"
+        );
+        assert_eq!(ContentError::new("no file").to_string(), "no file");
+    }
+
+    #[test]
+    fn chains_stop_at_cycles() {
+        let reporter = ContentReporter::default();
+        reporter.reference("a.enemy", "b.enemy");
+        reporter.reference("b.enemy", "a.enemy");
+        assert_eq!(reporter.chain("a.enemy"), ["b.enemy", "a.enemy"]);
+        assert_eq!(reporter.chain("b.enemy"), ["a.enemy", "b.enemy"]);
+
+        // files referenced from several places follow the first
+        reporter.reference("c.enemy", "bullet.bullet");
+        reporter.reference("d.enemy", "bullet.bullet");
+        reporter.reference("c.enemy", "bullet.bullet");
+        assert_eq!(
+            reporter.chain("bullet.bullet"),
+            ["c.enemy", "bullet.bullet"]
+        );
+        assert_eq!(reporter.chain("unknown.png"), ["unknown.png"]);
+    }
+
+    #[test]
+    fn errors_in_templates_are_put_after_their_file() {
+        let chain = |error: ContentError| error.chain;
+        let file = || vec!["test.enemy".to_string(), "a.enemy".to_string()];
+
+        // a template of a.enemy, which already named a.enemy itself
+        let template = ContentError {
+            chain: vec!["a.enemy".into(), "base.enemy".into()],
+            ..ContentError::new("")
+        };
+        assert_eq!(
+            chain(template.in_file(file())),
+            ["test.enemy", "a.enemy", "base.enemy"]
+        );
+
+        let other = ContentError {
+            chain: vec!["other.enemy".into()],
+            ..ContentError::new("")
+        };
+        assert_eq!(
+            chain(other.in_file(file())),
+            ["test.enemy", "a.enemy", "other.enemy"]
+        );
+        assert_eq!(
+            chain(ContentError::new("").in_file(file())),
+            ["test.enemy", "a.enemy"]
+        );
+    }
+}
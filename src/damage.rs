@@ -0,0 +1,212 @@
+// damage pipeline.
+//
+// nothing changes `Health` directly when it hurts something, it sends a `DamageEvent` saying who
+// hit who for how much. `apply_damage` then runs every hit through the same steps in order:
+// invulnerability, the attacker's damage multiplier, the target's defense (unless the hit is
+// armor piercing, and never below a fraction of the raw hit), the target's damage taken
+// multiplier. status effects on either side count, and the hit's own effects are applied.
+// only then is the health changed, and a `DamageDealt` with the final amount is sent for
+// anything that wants to know about it. hits that only apply status effects send one too.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bullet::detect_collisions,
+    health::mark_dead,
+    health::Health,
+    simulation::SimulationSet,
+    status_effect::{Effect, StatusEffects},
+};
+
+/// A hit always does at least this fraction of its raw damage, however high the defense
+pub const MIN_DAMAGE_FRACTION: f32 = 0.15;
+
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<DamageDealt>()
+            .add_system(
+                apply_damage
+                    .after(detect_collisions)
+                    .before(mark_dead)
+                    .in_set(SimulationSet::Damage)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .register_type::<DamageKind>()
+            .register_type::<DamageMultiplier>()
+            .register_type::<Invulnerable>();
+    }
+}
+
+#[derive(
+    Reflect, FromReflect, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, Debug,
+)]
+pub enum DamageKind {
+    /// reduced by the target's defense
+    #[default]
+    Normal,
+    /// ignores the target's defense
+    ArmorPiercing,
+    /// bleeding and poison, ignores the target's defense
+    OverTime,
+}
+
+/// A request to hurt `target`, before any mitigation
+pub struct DamageEvent {
+    pub target: Entity,
+    /// whoever dealt the damage, if anyone
+    pub source: Option<Entity>,
+    pub amount: u32,
+    pub damage_kind: DamageKind,
+    /// status effects to apply to the target and how long for, unless it's invulnerable
+    pub effects: Vec<(Effect, f32)>,
+}
+
+/// Damage that actually reached a target, after the whole pipeline
+pub struct DamageDealt {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    /// 0 if the hit only applied status effects
+    pub amount: u32,
+    /// how much of `amount` the target's shield took
+    pub absorbed: u32,
+    pub damage_kind: DamageKind,
+    /// status effects the hit applied
+    pub effects: Vec<Effect>,
+}
+
+/// Scales damage an entity deals and takes, 1.0 if missing
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+pub struct DamageMultiplier {
+    pub dealt: f32,
+    pub taken: f32,
+}
+
+impl Default for DamageMultiplier {
+    fn default() -> Self {
+        Self {
+            dealt: 1.0,
+            taken: 1.0,
+        }
+    }
+}
+
+/// Entities with this can't be damaged
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+pub struct Invulnerable;
+
+/// How much of a hit of `raw` damage gets through `defense`
+pub fn mitigate(raw: f32, defense: u32, damage_kind: DamageKind) -> f32 {
+    match damage_kind {
+        DamageKind::Normal => (raw - defense as f32).max(raw * MIN_DAMAGE_FRACTION),
+        DamageKind::ArmorPiercing | DamageKind::OverTime => raw,
+    }
+}
+
+/// Damage in whole points, so a hit that gets through at all always does at least 1
+pub fn round_damage(damage: f32) -> u32 {
+    if damage > 0.0 {
+        (damage.round() as u32).max(1)
+    } else {
+        0
+    }
+}
+
+pub fn apply_damage(
+    mut ev_damage: EventReader<DamageEvent>,
+    mut ev_dealt: EventWriter<DamageDealt>,
+    mut health_query: Query<(&mut Health, Option<&Invulnerable>)>,
+    multipliers: Query<&DamageMultiplier>,
+    mut status_effects: Query<&mut StatusEffects>,
+) {
+    for ev in ev_damage.iter() {
+        let Ok((mut health, invulnerable)) = health_query.get_mut(ev.target) else {
+            continue;
+        };
+        let target_effects = status_effects.get(ev.target).ok();
+        if invulnerable.is_some() || target_effects.is_some_and(StatusEffects::invulnerable) {
+            continue;
+        }
+        // damage over time neither starts nor is stopped by invulnerability after a hit
+        let over_time = ev.damage_kind == DamageKind::OverTime;
+        if !over_time && health.is_invulnerable() {
+            continue;
+        }
+        let defense =
+            target_effects.map_or(health.defense, |effects| effects.defense(health.defense));
+
+        // damage over time is the same whatever its source is doing now
+        let source = ev.source.filter(|_| !over_time);
+        let dealt = source
+            .and_then(|source| multipliers.get(source).ok())
+            .map_or(1.0, |multiplier| multiplier.dealt)
+            * source
+                .and_then(|source| status_effects.get(source).ok())
+                .map_or(1.0, StatusEffects::damage_dealt);
+        let taken = multipliers
+            .get(ev.target)
+            .map_or(1.0, |multiplier| multiplier.taken);
+
+        let mut applied = Vec::new();
+        if let Ok(mut effects) = status_effects.get_mut(ev.target) {
+            effects.apply_all(&ev.effects, ev.source);
+            applied.extend(ev.effects.iter().map(|(effect, _duration)| *effect));
+        }
+
+        let raw = ev.amount as f32 * dealt;
+        let amount = round_damage(mitigate(raw, defense, ev.damage_kind) * taken);
+        if amount == 0 && applied.is_empty() {
+            continue;
+        }
+
+        let mut absorbed = 0;
+        if amount > 0 {
+            absorbed = health.inflict_damage(amount);
+            if !over_time {
+                health.start_invulnerability();
+            }
+            if ev.source.is_some() {
+                health.last_attacker = ev.source;
+            }
+        }
+        ev_dealt.send(DamageDealt {
+            target: ev.target,
+            source: ev.source,
+            amount,
+            absorbed,
+            damage_kind: ev.damage_kind,
+            effects: applied,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(raw: f32, defense: u32, damage_kind: DamageKind) -> u32 {
+        round_damage(mitigate(raw, defense, damage_kind))
+    }
+
+    #[test]
+    fn defense_is_subtracted_from_normal_hits() {
+        assert_eq!(hit(40.0, 10, DamageKind::Normal), 30);
+        assert_eq!(hit(40.0, 10, DamageKind::ArmorPiercing), 40);
+        assert_eq!(hit(40.0, 10, DamageKind::OverTime), 40);
+    }
+
+    #[test]
+    fn hits_always_do_some_damage() {
+        // the minimum fraction
+        assert_eq!(hit(40.0, 100, DamageKind::Normal), 6);
+        // which rounds to nothing for weak hits
+        assert_eq!(hit(2.0, 100, DamageKind::Normal), 1);
+        assert_eq!(hit(0.4, 0, DamageKind::OverTime), 1);
+        assert_eq!(hit(0.0, 0, DamageKind::Normal), 0);
+        assert_eq!(round_damage(2.5), 3);
+        assert_eq!(round_damage(-1.0), 0);
+    }
+}
@@ -1,39 +1,69 @@
 use std::time::Duration;
 
 use bevy::{prelude::*, reflect::TypeUuid};
-use serde::Deserialize;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     bullet::{BulletBundle, BulletOptions},
-    player::Player,
-    shandle::SHandle,
+    bullet_pool::BulletPool,
+    health::Health,
+    player::{Player, Velocity},
+    player_death::AlivePlayer,
+    shandle::{SHandle, SHandleLoad},
+    simulation::SimRng,
+    status_effect::StatusEffects,
+    validate::{Problem, Validate},
 };
 
 use super::Ai;
 
+type Acting<'a> = (
+    Entity,
+    &'a mut Ai,
+    &'a mut Transform,
+    Option<&'a StatusEffects>,
+    Option<&'a Health>,
+);
+
 pub fn do_behaviors(
     mut commands: Commands,
-    mut query: Query<(&mut Ai, &mut Transform), Without<Player>>,
-    time: Res<Time>,
-    player: Query<&Transform, With<Player>>,
+    mut query: Query<Acting, Without<Player>>,
+    fixed_time: Res<FixedTime>,
+    mut rng: ResMut<SimRng>,
+    mut bullet_pool: ResMut<BulletPool>,
+    player: Query<(&Transform, &Velocity), AlivePlayer>,
     bullet_assets: Res<Assets<BulletOptions>>,
-    asset_server: Res<AssetServer>,
 ) {
-    let player_transform = player.single();
-    for (mut ai, mut transform) in query.iter_mut() {
+    // nobody to chase or shoot at while the player is dead
+    let Ok((player_transform, player_velocity)) = player.get_single() else {
+        return;
+    };
+    let no_effects = StatusEffects::default();
+    for (entity, mut ai, mut transform, effects, health) in query.iter_mut() {
+        // the dead stop acting while they fade away
+        if health.is_some_and(Health::is_dead) {
+            continue;
+        }
         let mut info = BehaviorInfo {
+            entity,
+            effects: effects.unwrap_or(&no_effects),
             transform: &mut transform,
-            player_transform: &player_transform,
-            time: &time,
+            player_transform,
+            player_velocity: player_velocity.0,
+            delta: fixed_time.period,
+            rng: &mut rng,
             commands: &mut commands,
+            bullet_pool: &mut bullet_pool,
             bullet_assets: &bullet_assets,
-            asset_server: &asset_server,
         };
         ai.do_behaviors(&mut info);
     }
 }
 
-#[derive(Component, Deserialize, TypeUuid, Clone, Reflect, FromReflect, Debug)]
+#[derive(
+    Component, Deserialize, Serialize, TypeUuid, Clone, Reflect, FromReflect, Debug, SHandleLoad,
+)]
 #[uuid = "b08c2b7c-a927-46d6-9344-755203047815"]
 pub enum Behavior {
     Idle,
@@ -47,41 +77,112 @@ pub enum Behavior {
     ShootAtPlayer {
         bullet: SHandle<BulletOptions>,
         interval: f32,
-        #[serde(skip_deserializing)]
+        /// aim where the player will be when the bullet gets there, if they keep moving
+        #[serde(default)]
+        predictive: bool,
+        /// how many degrees shots can randomly miss by, to either side
+        #[serde(default)]
+        inaccuracy: f32,
+        #[serde(skip)]
         timer: Timer,
+        /// volleys fired so far, to turn spiral patterns
+        #[serde(skip)]
+        volley: u32,
     },
 }
 
+impl Validate for Behavior {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        if let Behavior::ShootAtPlayer {
+            interval,
+            inaccuracy,
+            ..
+        } = self
+        {
+            // the interval becomes a timer's duration, which can't be negative or NaN
+            if interval.is_nan() || *interval <= 0.0 {
+                problems.push(Problem::new(
+                    format!("shot interval {interval} is not positive"),
+                    "interval",
+                ));
+            }
+            if inaccuracy.is_nan() || *inaccuracy < 0.0 {
+                problems.push(Problem::new(
+                    format!("inaccuracy {inaccuracy} is negative"),
+                    "inaccuracy",
+                ));
+            }
+        }
+    }
+}
+
 impl Behavior {
     pub fn perform(&mut self, info: &mut BehaviorInfo) {
         match self {
             Behavior::Idle => {}
             Behavior::Moving { x, y } => {
                 info.transform.translation +=
-                    Vec2::new(*x, *y).extend(0.0) * info.time.delta_seconds();
+                    info.effects.movement(Vec2::new(*x, *y)).extend(0.0) * info.delta.as_secs_f32();
             }
             Behavior::ChasePlayer { speed } => {
-                info.transform.translation += (info.player_transform.translation
-                    - info.transform.translation)
-                    .normalize_or_zero()
+                let direction = (info.player_transform.translation - info.transform.translation)
+                    .truncate()
+                    .normalize_or_zero();
+                info.transform.translation += info.effects.movement(direction).extend(0.0)
                     * *speed
-                    * info.time.delta_seconds();
+                    * info.delta.as_secs_f32();
             }
             Behavior::ShootAtPlayer {
                 bullet,
                 interval,
+                predictive,
+                inaccuracy,
                 timer,
+                volley,
             } => {
                 timer.set_duration(Duration::from_secs_f32(*interval));
-                timer.tick(info.time.delta());
+                // stunned enemies wait until they can shoot again
+                if !info.effects.can_shoot() {
+                    return;
+                }
+                timer.tick(info.delta.mul_f32(info.effects.rate_of_fire()));
                 if timer.just_finished() {
-                    let bullet_options = info.bullet_assets.get(&bullet.unwrap()).unwrap();
-                    info.commands.spawn(BulletBundle::new(
-                        bullet_options.clone(),
-                        4.0,
-                        info.transform.translation.truncate(),
-                        info.asset_server,
-                    ));
+                    let Ok(bullet_handle) = bullet.handle() else {
+                        timer.reset();
+                        return;
+                    };
+                    // shared bullet files load separately and may not be ready yet
+                    if let Some(bullet_options) = info.bullet_assets.get(&bullet_handle) {
+                        let position = info.transform.translation.truncate();
+                        let target = info.player_transform.translation.truncate();
+                        let target = if *predictive {
+                            let speed = bullet_options.speed;
+                            lead_target(position, target, info.player_velocity, speed)
+                        } else {
+                            target
+                        };
+                        let offset = target - position;
+                        let mut direction = offset.y.atan2(offset.x);
+                        if *inaccuracy > 0.0 {
+                            direction += info
+                                .rng
+                                .rng()
+                                .gen_range(-*inaccuracy..=*inaccuracy)
+                                .to_radians();
+                        }
+                        info.bullet_pool.spawn_batch(
+                            info.commands,
+                            BulletBundle::volley(
+                                bullet_handle,
+                                bullet_options,
+                                info.entity,
+                                direction,
+                                *volley,
+                                info.transform.translation.truncate(),
+                            ),
+                        );
+                        *volley = volley.wrapping_add(1);
+                    }
                     timer.reset();
                 }
             }
@@ -89,11 +190,96 @@ impl Behavior {
     }
 }
 
+/// Where to shoot at a target moving in a straight line so a bullet fired from `position`
+/// at `speed` hits it, or the target itself if the bullet can't catch up
+fn lead_target(position: Vec2, target: Vec2, velocity: Vec2, speed: f32) -> Vec2 {
+    // solve |offset + velocity * t| = speed * t for the earliest time t > 0
+    let offset = target - position;
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+
+    let time = if a.abs() < f32::EPSILON {
+        (b != 0.0).then(|| -c / b)
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        (discriminant >= 0.0)
+            .then(|| {
+                let root = discriminant.sqrt();
+                [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+                    .into_iter()
+                    .filter(|t| *t > 0.0)
+                    .min_by(f32::total_cmp)
+            })
+            .flatten()
+    };
+
+    match time {
+        Some(time) if time > 0.0 => target + velocity * time,
+        _ => target,
+    }
+}
+
 pub struct BehaviorInfo<'a, 'w, 's> {
+    pub entity: Entity,
+    pub effects: &'a StatusEffects,
     pub transform: &'a mut Transform,
-    pub time: &'a Time,
+    /// length of a tick
+    pub delta: Duration,
+    pub rng: &'a mut SimRng,
     pub player_transform: &'a Transform,
+    pub player_velocity: Vec2,
     pub commands: &'a mut Commands<'w, 's>,
+    pub bullet_pool: &'a mut BulletPool,
     pub bullet_assets: &'a Assets<BulletOptions>,
-    pub asset_server: &'a AssetServer,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leads_moving_targets() {
+        let position = Vec2::ZERO;
+        let target = Vec2::new(10.0, 0.0);
+        assert_eq!(lead_target(position, target, Vec2::ZERO, 10.0), target);
+
+        // crossing the line of fire, |(10, 5t)| = 10t when t = 2 / sqrt(3)
+        let aim = lead_target(position, target, Vec2::new(0.0, 5.0), 10.0);
+        let time = 2.0 / 3.0_f32.sqrt();
+        assert!(aim.abs_diff_eq(Vec2::new(10.0, 5.0 * time), 1e-4), "{aim}");
+        assert!((aim.distance(position) - 10.0 * time).abs() < 1e-4);
+
+        // running away faster than or as fast as the bullet
+        for speed in [20.0, 10.0] {
+            let velocity = Vec2::new(speed, 0.0);
+            assert_eq!(lead_target(position, target, velocity, 10.0), target);
+        }
+    }
+
+    #[test]
+    fn shots_need_a_positive_interval() {
+        let shoot = |interval, inaccuracy| {
+            let mut problems = Vec::new();
+            Behavior::ShootAtPlayer {
+                bullet: SHandle::Serialized("a.bullet".into()),
+                interval,
+                predictive: false,
+                inaccuracy,
+                timer: default(),
+                volley: 0,
+            }
+            .validate(&mut problems);
+            problems
+                .into_iter()
+                .map(|problem| problem.needle)
+                .collect::<Vec<_>>()
+        };
+        assert!(shoot(0.5, 0.0).is_empty());
+        assert!(shoot(0.5, 10.0).is_empty());
+        for interval in [0.0, -1.0, f32::NAN] {
+            assert_eq!(shoot(interval, 0.0), ["interval"]);
+        }
+        assert_eq!(shoot(0.5, -5.0), ["inaccuracy"]);
+    }
 }
@@ -5,20 +5,34 @@ use self::{
     behaviors::{do_behaviors, Behavior, BehaviorInfo},
     transitions::{do_transitions, Transition, TransitionInfo},
 };
-use crate::loader;
+use crate::{
+    loader,
+    shandle::{SHandle, SHandleLoad},
+    simulation::SimulationSet,
+    validate::{in_field, Problem, Validate},
+};
 use bevy::{prelude::*, reflect::TypeUuid};
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{
+    de::{value::MapAccessDeserializer, EnumAccess, MapAccess, VariantAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::HashMap, fmt};
 
 // an ai implementation using a finite state machine.
 
 pub struct AiPlugin;
 impl Plugin for AiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(do_behaviors).add_system(do_transitions);
-        // .add_system(load_ais)
-        // .add_asset::<Ai>()
-        // .init_asset_loader::<AiLoader>();
+        app.add_systems(
+            (do_transitions, do_behaviors)
+                .chain()
+                .in_set(SimulationSet::Enemies)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_system(load_ais)
+        .add_system(reload_ais)
+        .add_asset::<Ai>()
+        .init_asset_loader::<AiLoader>();
     }
 }
 
@@ -44,24 +58,116 @@ pub fn test_ai() -> Ai {
     }
 }
 
-// pub fn load_ais(
-//     mut commands: Commands,
-//     query: Query<(Entity, &Handle<Ai>)>,
-//     assets: Res<Assets<Ai>>,
-// ) {
-//     for (entity, handle) in query.iter() {
-//         if let Some(ai) = assets.get(handle) {
-//             commands
-//                 .entity(entity)
-//                 .insert(ai.clone())
-//                 .remove::<Handle<Ai>>();
-//         } else {
-//             println!("this AI thang taking fookin aages to load init");
-//         }
-//     }
-// }
-
-#[derive(Component, Deserialize, TypeUuid, Clone, FromReflect, Reflect, Debug)]
+/// Give entities using a shared ai file their own copy of the ai once it's loaded
+pub fn load_ais(
+    mut commands: Commands,
+    query: Query<(Entity, &Handle<Ai>), Without<Ai>>,
+    assets: Res<Assets<Ai>>,
+) {
+    for (entity, handle) in query.iter() {
+        if let Some(ai) = assets.get(handle) {
+            commands.entity(entity).insert(ai.clone());
+        }
+    }
+}
+
+/// Apply changes to modified ai files to the entities using them
+pub fn reload_ais(
+    mut ev_asset: EventReader<AssetEvent<Ai>>,
+    assets: Res<Assets<Ai>>,
+    mut query: Query<(&Handle<Ai>, &mut Ai)>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Modified { handle } = ev {
+            if let Some(new_ai) = assets.get(handle) {
+                for (ai_handle, mut ai) in &mut query {
+                    if ai_handle == handle {
+                        ai.reload(new_ai);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The ai of an enemy, either written inline as `Ai(...)` or shared through an `.ai` file
+/// with `Serialized("path.ai")`
+#[derive(Clone, Reflect, FromReflect, Debug, SHandleLoad)]
+pub enum EnemyAi {
+    Inline(Ai),
+    Shared(SHandle<Ai>),
+}
+
+impl Validate for EnemyAi {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        // shared ai files are checked on their own
+        if let EnemyAi::Inline(ai) = self {
+            ai.validate(problems);
+        }
+    }
+}
+
+// written by hand so both forms can be used without wrapping them in another variant
+impl<'de> Deserialize<'de> for EnemyAi {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EnemyAiVisitor;
+
+        impl<'de> Visitor<'de> for EnemyAiVisitor {
+            type Value = EnemyAi;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an inline `Ai` or a `Serialized` path to an ai file")
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+                #[derive(Deserialize)]
+                enum Variant {
+                    Ai,
+                    Serialized,
+                }
+
+                match data.variant()? {
+                    (Variant::Ai, access) => access
+                        .struct_variant(&["phases", "current"], InlineAiVisitor)
+                        .map(EnemyAi::Inline),
+                    (Variant::Serialized, access) => access
+                        .newtype_variant::<String>()
+                        .map(|path| EnemyAi::Shared(SHandle::Serialized(path))),
+                }
+            }
+        }
+
+        struct InlineAiVisitor;
+
+        impl<'de> Visitor<'de> for InlineAiVisitor {
+            type Value = Ai;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an ai")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                Ai::deserialize(MapAccessDeserializer::new(map))
+            }
+        }
+
+        deserializer.deserialize_enum("EnemyAi", &["Ai", "Serialized"], EnemyAiVisitor)
+    }
+}
+
+// written the same way it's read, `Ai(...)` or `Serialized("path.ai")`
+impl Serialize for EnemyAi {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            EnemyAi::Inline(ai) => ai.serialize(serializer),
+            EnemyAi::Shared(ai) => ai.serialize(serializer),
+        }
+    }
+}
+
+#[derive(
+    Component, Deserialize, Serialize, TypeUuid, Clone, FromReflect, Reflect, Debug, SHandleLoad,
+)]
 #[uuid = "b08c2b7c-a927-46d6-9344-755203047812"]
 pub struct Ai {
     pub phases: HashMap<String, Phase>,
@@ -76,6 +182,30 @@ impl Ai {
             }
         }
     }
+    /// Take the phases of a reloaded definition, staying in the current phase if it still exists
+    pub fn reload(&mut self, ai: &Ai) {
+        let current = std::mem::take(&mut self.current);
+        *self = ai.clone();
+        if self.phases.contains_key(&current) {
+            self.current = current;
+        }
+    }
+
+    /// Health fractions at which some phase moves on, lowest first
+    pub fn health_thresholds(&self) -> Vec<f32> {
+        let mut thresholds: Vec<f32> = self
+            .phases
+            .values()
+            .flat_map(|phase| &phase.transitions)
+            .map(|(transition, _dest)| match transition {
+                Transition::HealthLessThan(threshold) => *threshold,
+            })
+            .collect();
+        thresholds.sort_by(f32::total_cmp);
+        thresholds.dedup();
+        thresholds
+    }
+
     pub fn do_behaviors(&mut self, info: &mut BehaviorInfo) {
         for behavior in self
             .phases
@@ -89,11 +219,58 @@ impl Ai {
     }
 }
 
-#[derive(Component, Deserialize, TypeUuid, Clone, Reflect, FromReflect, Debug)]
+impl Validate for Ai {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        if !self.phases.contains_key(&self.current) {
+            problems.push(
+                Problem::new(
+                    format!("starting phase \"{}\" does not exist", self.current),
+                    format!("\"{}\"", self.current),
+                )
+                .at("current"),
+            );
+        }
+
+        let mut names: Vec<_> = self.phases.keys().collect();
+        names.sort();
+        for name in names {
+            in_field("phases", problems, |problems| {
+                in_field(name, problems, |problems| {
+                    in_field("behaviors", problems, |problems| {
+                        for (i, behavior) in self.phases[name].behaviors.iter().enumerate() {
+                            in_field(&i.to_string(), problems, |problems| {
+                                behavior.validate(problems)
+                            });
+                        }
+                    })
+                })
+            });
+            for (_transition, dest) in &self.phases[name].transitions {
+                if !self.phases.contains_key(dest) {
+                    problems.push(
+                        Problem::new(
+                            format!(
+                                "phase \"{name}\" transitions to \"{dest}\" which does not exist"
+                            ),
+                            format!("\"{dest}\""),
+                        )
+                        .at("phases")
+                        .at(name)
+                        .at("transitions"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[derive(
+    Component, Deserialize, Serialize, TypeUuid, Clone, Reflect, FromReflect, Debug, SHandleLoad,
+)]
 #[uuid = "b08c2b7c-a927-46d6-9344-755203047813"]
 pub struct Phase {
     pub behaviors: Vec<Behavior>,
     pub transitions: Vec<(Transition, String)>,
 }
 
-// loader!(Ai, AiLoader, &["ai"]);
+loader!(Ai, AiLoader, &["ai"]);
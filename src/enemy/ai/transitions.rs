@@ -1,11 +1,14 @@
 use bevy::{prelude::*, reflect::TypeUuid};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::health::Health;
+use crate::{health::Health, shandle::SHandleLoad};
 
 use super::Ai;
 pub fn do_transitions(mut query: Query<(&mut Ai, &Health)>) {
     for (mut ai, health) in query.iter_mut() {
+        if health.is_dead() {
+            continue;
+        }
         let info = TransitionInfo {
             health_frac: health.frac(),
         };
@@ -13,7 +16,9 @@ pub fn do_transitions(mut query: Query<(&mut Ai, &Health)>) {
     }
 }
 
-#[derive(Component, Deserialize, TypeUuid, Clone, Debug, FromReflect, Reflect)]
+#[derive(
+    Component, Deserialize, Serialize, TypeUuid, Clone, Debug, FromReflect, Reflect, SHandleLoad,
+)]
 #[uuid = "b08c2b7c-a927-46d6-9344-755203047814"]
 pub enum Transition {
     HealthLessThan(f32),
@@ -0,0 +1,312 @@
+// what happens when an enemy dies.
+//
+// a dead enemy stops acting and can't be hit right away (its ai and the spatial hash skip the
+// dead), runs the `on_death` actions of its options, then fades out and is despawned.
+
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, reflect::TypeUuid};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    billboard_sprite::BillboardSpriteBundle,
+    bullet::{BulletBundle, BulletOptions},
+    bullet_pool::BulletPool,
+    health::{mark_dead, DeathEvent},
+    shandle::{SHandle, SHandleLoad},
+    simulation::SimulationSet,
+    validate::{Problem, Validate},
+};
+
+use super::{EnemyBundle, EnemyOptions};
+
+/// seconds a dead enemy takes to fade away
+pub const CORPSE_FADE: f32 = 1.0;
+
+pub struct EnemyDeathPlugin;
+
+impl Plugin for EnemyDeathPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (handle_enemy_death.after(mark_dead), fade_out)
+                .chain()
+                .in_set(SimulationSet::Deaths)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .register_type::<Fading>();
+    }
+}
+
+/// Something an enemy does when it dies
+#[derive(Deserialize, Serialize, TypeUuid, Reflect, FromReflect, Debug, SHandleLoad)]
+#[uuid = "3f6b3c1e-6a0d-4f5e-9d37-51a4c2b1e7d0"]
+pub enum OnDeath {
+    /// fire `count` bullets evenly spread in every direction
+    BulletRing {
+        bullet: SHandle<BulletOptions>,
+        count: u32,
+    },
+    /// spawn `count` enemies in a circle `radius` around where it died
+    SpawnMinions {
+        enemy: SHandle<EnemyOptions>,
+        count: u32,
+        #[serde(default)]
+        radius: f32,
+    },
+    /// show a sprite that fades away over `duration` seconds
+    Effect {
+        sprite: SHandle<Image>,
+        duration: f32,
+    },
+}
+
+impl Validate for OnDeath {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        match self {
+            OnDeath::BulletRing { bullet, count } if *count == 0 => {
+                problems.push(Problem::new("bullet ring has no bullets", bullet.path()));
+            }
+            OnDeath::SpawnMinions { enemy, count, .. } if *count == 0 => {
+                problems.push(Problem::new("no minions are spawned", enemy.path()));
+            }
+            OnDeath::SpawnMinions { radius, .. } if *radius < 0.0 => {
+                problems.push(Problem::new(
+                    format!("minion radius {radius} is negative"),
+                    "radius",
+                ));
+            }
+            OnDeath::Effect { duration, .. } if *duration <= 0.0 => {
+                problems.push(Problem::new(
+                    format!("effect duration {duration} is not positive"),
+                    "duration",
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Fades the sprite away and despawns the entity once the timer is done
+#[derive(Component, Reflect, Default, Debug)]
+pub struct Fading {
+    pub timer: Timer,
+}
+
+impl Fading {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+}
+
+pub fn handle_enemy_death(
+    mut commands: Commands,
+    mut ev_death: EventReader<DeathEvent>,
+    query: Query<(&Transform, &Handle<EnemyOptions>)>,
+    enemy_assets: Res<Assets<EnemyOptions>>,
+    bullet_assets: Res<Assets<BulletOptions>>,
+    mut bullet_pool: ResMut<BulletPool>,
+) {
+    for ev in ev_death.iter() {
+        let Ok((transform, handle)) = query.get(ev.entity) else {
+            continue;
+        };
+        commands.entity(ev.entity).insert(Fading::new(CORPSE_FADE));
+
+        let Some(options) = enemy_assets.get(handle) else {
+            continue;
+        };
+        let position = transform.translation.truncate();
+        for action in &options.on_death {
+            match action {
+                OnDeath::BulletRing { bullet, count } => {
+                    let Ok(bullet_handle) = bullet.handle() else {
+                        continue;
+                    };
+                    let Some(bullet_options) = bullet_assets.get(&bullet_handle) else {
+                        continue;
+                    };
+                    bullet_pool.spawn_batch(
+                        &mut commands,
+                        (0..*count).filter_map(|i| {
+                            BulletBundle::new(
+                                bullet_handle.clone(),
+                                bullet_options,
+                                ev.entity,
+                                TAU * i as f32 / *count as f32,
+                                position,
+                            )
+                        }),
+                    );
+                }
+                OnDeath::SpawnMinions {
+                    enemy,
+                    count,
+                    radius,
+                } => {
+                    let Ok(enemy_handle) = enemy.handle() else {
+                        continue;
+                    };
+                    for i in 0..*count {
+                        let offset = Vec2::from_angle(TAU * i as f32 / *count as f32) * *radius;
+                        commands.spawn((
+                            EnemyBundle {
+                                options: enemy_handle.clone(),
+                                spatial_bundle: SpatialBundle::from_transform(
+                                    Transform::from_translation(
+                                        (position + offset).extend(transform.translation.z),
+                                    ),
+                                ),
+                            },
+                            Name::new("Minion"),
+                        ));
+                    }
+                }
+                OnDeath::Effect { sprite, duration } => {
+                    let Ok(sprite_handle) = sprite.handle() else {
+                        continue;
+                    };
+                    let mut effect = BillboardSpriteBundle::new_anchored(sprite_handle);
+                    effect.transform.translation = transform.translation;
+                    commands.spawn((effect, Fading::new(*duration), Name::new("Death Effect")));
+                }
+            }
+        }
+    }
+}
+
+pub fn fade_out(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Fading, &mut Sprite)>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (entity, mut fading, mut sprite) in &mut query {
+        fading.timer.tick(fixed_time.period);
+        sprite.color.set_a(fading.timer.percent_left());
+        if fading.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        bullet::{BulletPlugin, Team},
+        collision::{HitRadius, SpatialHash},
+        damage::{DamageEvent, DamageKind, DamagePlugin},
+        enemy::{behaviors::Behavior, Ai, EnemyPlugin, Phase},
+        health::{Health, HealthPlugin},
+        items::item::Item,
+        player::{Player, Velocity},
+        simulation::{Interpolated, SimulationPlugin, TICK_RATE},
+        testing::{asset_app, MemoryAssetIo},
+    };
+
+    #[test]
+    fn dead_enemies_stop_acting_and_fade_away() {
+        let mut app = asset_app(MemoryAssetIo::default());
+        app.add_plugin(SimulationPlugin)
+            .add_plugin(HealthPlugin)
+            .add_plugin(DamagePlugin)
+            .add_plugin(BulletPlugin)
+            .add_plugin(EnemyPlugin)
+            .add_asset::<Item>();
+
+        let bullet = app
+            .world
+            .resource_mut::<Assets<BulletOptions>>()
+            .add(BulletOptions {
+                sprite: SHandle::Loaded {
+                    path: "bullet.png".into(),
+                    handle: Handle::default(),
+                },
+                ..default()
+            });
+        let ai = Ai {
+            phases: HashMap::from([(
+                "Start".into(),
+                Phase {
+                    behaviors: vec![
+                        Behavior::Moving { x: 1.0, y: 0.0 },
+                        Behavior::ShootAtPlayer {
+                            bullet: SHandle::Loaded {
+                                path: "bullet.bullet".into(),
+                                handle: bullet,
+                            },
+                            interval: 0.05,
+                            predictive: false,
+                            inaccuracy: 0.0,
+                            timer: default(),
+                            volley: 0,
+                        },
+                    ],
+                    transitions: Vec::new(),
+                },
+            )]),
+            current: "Start".into(),
+        };
+        app.world.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(0.0, -5.0, 0.0)),
+            Player,
+            Velocity::default(),
+        ));
+        let transform = Transform::from_xyz(0.0, 5.0, 0.0);
+        let enemy = app
+            .world
+            .spawn((
+                SpriteBundle {
+                    transform,
+                    ..default()
+                },
+                Handle::<EnemyOptions>::default(),
+                Health::new(10, Team::Enemy),
+                HitRadius(0.5),
+                Interpolated::new(&transform),
+                ai,
+            ))
+            .id();
+
+        // alive, it moves and shoots
+        for _ in 0..10 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        let shots = app.world.resource::<BulletPool>().stats().spawned;
+        assert!(shots > 0);
+        app.world.send_event(DamageEvent {
+            target: enemy,
+            source: None,
+            amount: 100,
+            damage_kind: DamageKind::ArmorPiercing,
+            effects: Vec::new(),
+        });
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert!(app.world.get::<Health>(enemy).unwrap().is_dead());
+        let position = app.world.get::<Transform>(enemy).unwrap().translation;
+
+        for _ in 0..10 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        assert_eq!(
+            app.world.get::<Transform>(enemy).unwrap().translation,
+            position
+        );
+        assert_eq!(app.world.resource::<BulletPool>().stats().spawned, shots);
+        assert_eq!(app.world.resource::<BulletPool>().stats().reused, 0);
+        assert!(app
+            .world
+            .resource::<SpatialHash>()
+            .query(position.truncate())
+            .all(|target| target.entity != enemy));
+
+        // with the ticks above, as ticks don't add up to exactly `CORPSE_FADE`
+        for _ in 0..(CORPSE_FADE * TICK_RATE) as u32 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        assert!(app.world.get_entity(enemy).is_none());
+    }
+}
@@ -1,54 +1,82 @@
 use crate::{
-    billboard_sprite::SPRITE8,
     health::DeathEvent,
-    items::{
-        dropped_item::{DroppedItem, DroppedItemBundle},
-        item::Item,
-    },
+    items::{dropped_item::DroppedItemBundle, item::Item},
 };
 use bevy::{prelude::*, reflect::TypeUuid};
 use rand::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use super::SHandle;
+use super::{death::handle_enemy_death, SHandle};
+use crate::{
+    shandle::SHandleLoad,
+    simulation::{SimRng, SimulationSet},
+    validate::{Problem, Validate},
+};
 
 pub struct DropTablePlugin;
 
 impl Plugin for DropTablePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(drop_dead_entity_tables)
-            .register_type::<DropTable>();
+        app.add_system(
+            drop_dead_entity_tables
+                .after(handle_enemy_death)
+                .in_set(SimulationSet::Deaths)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .register_type::<DropTable>();
     }
 }
 
-#[derive(Component, Clone, Reflect, TypeUuid, Deserialize, Debug, FromReflect)]
+#[derive(
+    Component, Clone, Reflect, TypeUuid, Deserialize, Serialize, Debug, FromReflect, SHandleLoad,
+)]
 #[uuid = "0222cefa-f22c-4347-8166-38831647325c"]
 pub struct DropTable {
     pub drops: Vec<(SHandle<Item>, f32)>,
 }
 
 impl DropTable {
-    pub fn get_items(&self) -> Vec<SHandle<Item>> {
+    pub fn get_items(&self, rng: &mut impl Rng) -> Vec<SHandle<Item>> {
         self.drops
             .iter()
-            .filter(|(_item, chance)| thread_rng().gen_bool(*chance as f64))
+            .filter(|(_item, chance)| rng.gen_bool(*chance as f64))
             .map(|(item, _chance)| item)
             .cloned()
             .collect()
     }
 }
 
+impl Validate for DropTable {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        for (i, (item, chance)) in self.drops.iter().enumerate() {
+            if !(0.0..=1.0).contains(chance) {
+                problems.push(
+                    Problem::new(
+                        format!("drop chance {chance} is not between 0 and 1"),
+                        item.path(),
+                    )
+                    .at("drops")
+                    .at(i.to_string()),
+                );
+            }
+        }
+    }
+}
+
 pub fn drop_dead_entity_tables(
     mut commands: Commands,
     query: Query<(&DropTable, &Transform)>,
     mut ev_death: EventReader<DeathEvent>,
     assets: Res<Assets<Item>>,
+    mut rng: ResMut<SimRng>,
 ) {
     for ev in ev_death.iter() {
-        if let Ok((drop_table, transform)) = query.get(ev.0) {
-            for item in drop_table.get_items() {
+        if let Ok((drop_table, transform)) = query.get(ev.entity) {
+            for item in drop_table.get_items(rng.rng()) {
                 dbg!(&item);
-                commands.spawn(DroppedItemBundle::new(item, transform.translation, &assets));
+                if let Some(bundle) = DroppedItemBundle::new(item, transform.translation, &assets) {
+                    commands.spawn(bundle);
+                }
             }
         }
     }
@@ -1,26 +1,27 @@
 pub mod ai;
+pub mod death;
 pub mod drop_table;
 
 pub use ai::*;
-use async_trait::async_trait;
-use bevy::{
-    asset::{AssetPath, LoadContext, LoadedAsset},
-    prelude::*,
-    reflect::TypeUuid,
-    render::texture::CompressedImageFormats,
-};
-use serde::Deserialize;
-use std::{fmt::Debug, path::Path};
+use bevy::{prelude::*, reflect::TypeUuid};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
 
 use crate::{
-    billboard_sprite::{BillboardSpriteBundle, SPRITE8},
+    billboard_sprite::BillboardSpriteBundle,
+    collision::{default_hit_radius, HitRadius},
     health::Health,
-    items::item::Item,
     loader,
-    shandle::{load_ron, load_sprite, store_ron, SHandle, SHandleLoad},
+    shandle::{SHandle, SHandleLoad},
+    simulation::Interpolated,
+    status_effect::StatusEffects,
+    validate::{in_field, Problem, Validate},
 };
 
-use self::drop_table::{DropTable, DropTablePlugin};
+use self::{
+    death::{EnemyDeathPlugin, OnDeath},
+    drop_table::{DropTable, DropTablePlugin},
+};
 
 pub struct EnemyPlugin;
 impl Plugin for EnemyPlugin {
@@ -29,41 +30,53 @@ impl Plugin for EnemyPlugin {
             .add_asset::<EnemyOptions>()
             .add_plugin(AiPlugin)
             .add_plugin(DropTablePlugin)
-            .add_system(load_enemies);
+            .add_plugin(EnemyDeathPlugin)
+            .add_system(load_enemies)
+            .add_system(reload_enemies);
     }
 }
 
-#[derive(Deserialize, TypeUuid, Reflect, FromReflect, Debug)]
+#[derive(Deserialize, Serialize, TypeUuid, Reflect, FromReflect, Debug, SHandleLoad)]
 #[uuid = "57422828-c764-11ed-afa1-0242ac120002"]
 pub struct EnemyOptions {
     pub health: Health,
     pub sprite: SHandle<Image>,
-    pub ai: Ai,
+    pub ai: EnemyAi,
     pub drop_table: DropTable,
+    /// how close bullets have to get to hit the enemy
+    #[serde(default = "default_hit_radius")]
+    pub hit_radius: f32,
+    /// what happens when the enemy dies, before it fades away
+    // not reflected, minions would make the type contain itself
+    #[serde(default)]
+    #[reflect(ignore)]
+    pub on_death: Vec<OnDeath>,
+    /// shows a big health bar at the top of the screen instead of one over the enemy
+    #[serde(default)]
+    pub boss: bool,
 }
 
-#[derive(Default)]
-pub struct EnemyOptionsLoader;
-
-impl bevy::asset::AssetLoader for EnemyOptionsLoader {
-    fn load<'a>(
-        &'a self,
-        bytes: &'a [u8],
-        load_context: &'a mut bevy::asset::LoadContext,
-    ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
-        Box::pin(async move {
-            let mut shandle: SHandle<EnemyOptions> =
-                SHandle::Serialized(load_context.path().to_string_lossy().to_string());
-            shandle.shandle_load(load_context, true).await?;
-
-            Ok(())
-        })
-    }
-    fn extensions(&self) -> &[&str] {
-        &["enemy"]
+impl Validate for EnemyOptions {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        in_field("health", problems, |problems| {
+            self.health.validate(problems)
+        });
+        in_field("ai", problems, |problems| self.ai.validate(problems));
+        in_field("drop_table", problems, |problems| {
+            self.drop_table.validate(problems)
+        });
+        in_field("on_death", problems, |problems| {
+            for (i, action) in self.on_death.iter().enumerate() {
+                in_field(&i.to_string(), problems, |problems| {
+                    action.validate(problems)
+                });
+            }
+        });
     }
 }
 
+loader!(EnemyOptions, EnemyOptionsLoader, &["enemy"]);
+
 #[derive(Bundle)]
 pub struct EnemyBundle {
     pub options: Handle<EnemyOptions>,
@@ -72,25 +85,129 @@ pub struct EnemyBundle {
 
 pub fn load_enemies(
     mut commands: Commands,
-    query: Query<(Entity, &Handle<EnemyOptions>)>,
+    query: Query<(Entity, &Handle<EnemyOptions>, &Transform), Without<Health>>,
     mut assets: ResMut<Assets<EnemyOptions>>,
     asset_server: Res<AssetServer>,
 ) {
-    for (entity, handle) in query.iter() {
+    for (entity, handle, transform) in query.iter() {
         if let Some(options) = assets.get_mut(handle) {
-            if let SHandle::Loaded(sprite_handle) = &options.sprite {
-                commands
-                    .entity(entity)
-                    .insert((
-                        options.health.clone(),
-                        BillboardSpriteBundle::new_anchored(sprite_handle.clone()),
-                        options.drop_table.clone(),
-                        options.ai.clone(),
-                    ))
-                    .remove::<Handle<EnemyOptions>>();
+            if let SHandle::Loaded {
+                handle: sprite_handle,
+                ..
+            } = &options.sprite
+            {
+                // the options handle stays on the entity so it can be hot reloaded
+                let mut enemy = commands.entity(entity);
+                enemy.insert((
+                    options.health.clone(),
+                    BillboardSpriteBundle::new_anchored(sprite_handle.clone()),
+                    options.drop_table.clone(),
+                    HitRadius(options.hit_radius),
+                    StatusEffects::default(),
+                    Interpolated::new(transform),
+                ));
+                match &options.ai {
+                    EnemyAi::Inline(ai) => {
+                        enemy.insert(ai.clone());
+                    }
+                    // the ai gets inserted by load_ais once it's loaded
+                    EnemyAi::Shared(ai_handle) => {
+                        if let Ok(ai_handle) = ai_handle.handle() {
+                            enemy.insert(ai_handle);
+                        }
+                    }
+                }
             } else {
                 options.sprite.load(&asset_server);
             }
         }
     }
 }
+
+type Reloaded<'a> = (
+    Entity,
+    &'a Handle<EnemyOptions>,
+    &'a mut Health,
+    Option<&'a mut Ai>,
+    Option<&'a Handle<Ai>>,
+    &'a mut DropTable,
+    &'a mut HitRadius,
+    &'a mut Handle<Image>,
+);
+
+/// Apply changes to modified enemy files to already spawned enemies
+pub fn reload_enemies(
+    mut commands: Commands,
+    mut ev_asset: EventReader<AssetEvent<EnemyOptions>>,
+    assets: Res<Assets<EnemyOptions>>,
+    ai_assets: Res<Assets<Ai>>,
+    mut query: Query<Reloaded>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Modified { handle } = ev {
+            if let Some(options) = assets.get(handle) {
+                for (
+                    entity,
+                    enemy_handle,
+                    mut health,
+                    ai,
+                    ai_handle,
+                    mut drop_table,
+                    mut hit_radius,
+                    mut texture,
+                ) in &mut query
+                {
+                    if enemy_handle != handle {
+                        continue;
+                    }
+                    health.reload(&options.health);
+                    *drop_table = options.drop_table.clone();
+                    hit_radius.0 = options.hit_radius;
+                    if let SHandle::Loaded {
+                        handle: sprite_handle,
+                        ..
+                    } = &options.sprite
+                    {
+                        *texture = sprite_handle.clone();
+                    }
+                    // last, so switching ais can skip the rest of the loop
+                    match (&options.ai, ai, ai_handle) {
+                        (EnemyAi::Inline(new_ai), Some(mut ai), ai_handle) => {
+                            ai.reload(new_ai);
+                            // switched from a shared ai file
+                            if ai_handle.is_some() {
+                                commands.entity(entity).remove::<Handle<Ai>>();
+                            }
+                        }
+                        // the shared ai file it switched from was never loaded
+                        (EnemyAi::Inline(new_ai), None, _) => {
+                            commands
+                                .entity(entity)
+                                .remove::<Handle<Ai>>()
+                                .insert(new_ai.clone());
+                        }
+                        (EnemyAi::Shared(shared), ai, ai_handle) => {
+                            // the same shared ai file is reloaded on its own
+                            let Ok(new_handle) = shared.handle() else {
+                                continue;
+                            };
+                            if ai_handle == Some(&new_handle) {
+                                continue;
+                            }
+                            // switched to another shared ai file, or from an inline ai
+                            let mut enemy = commands.entity(entity);
+                            match (ai_assets.get(&new_handle), ai) {
+                                (Some(new_ai), Some(mut ai)) => ai.reload(new_ai),
+                                // load_ais inserts it once the file is loaded
+                                _ => {
+                                    enemy.remove::<Ai>();
+                                }
+                            }
+                            enemy.insert(new_handle);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+// numbers that float up from entities when they're hurt or healed.
+//
+// every frame, all the damage an entity took is added up into a single number per kind, along
+// with whatever it healed and the status effects it got. healing is merged over a short window
+// instead, regen would show a number for every point otherwise. the numbers are billboarded text
+// that rises and fades away.
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    billboard_sprite::{billboard_rotation, BillboardSprite},
+    bullet::Team,
+    damage::{DamageDealt, DamageKind},
+    font::GameFont,
+    health::Health,
+    player::Player,
+    status_effect::Effect,
+};
+
+/// seconds a number is shown for
+pub const NUMBER_LIFETIME: f32 = 0.8;
+/// how fast numbers rise, in units per second
+pub const NUMBER_RISE_SPEED: f32 = 1.0;
+/// height above an entity's origin that numbers start at
+pub const NUMBER_HEIGHT: f32 = 1.2;
+/// seconds an entity's healing is added up over before its next heal number is shown
+pub const HEAL_MERGE_WINDOW: f32 = 0.5;
+
+// text is rendered in pixels, this makes it half a unit tall
+const FONT_SIZE: f32 = 64.0;
+const TEXT_SCALE: f32 = 1.0 / 128.0;
+
+pub struct FloatingNumbersPlugin;
+
+impl Plugin for FloatingNumbersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameFont>()
+            .add_system(spawn_floating_numbers)
+            .add_system(rise_floating_numbers)
+            .register_type::<FloatingNumber>();
+    }
+}
+
+/// What a floating number is showing
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum NumberKind {
+    Damage,
+    ArmorPiercing,
+    Heal,
+    /// damage the shield took instead of health
+    ShieldAbsorb,
+    /// names of the status effects that were applied
+    Status,
+}
+
+impl NumberKind {
+    pub fn color(self, team: &Team) -> Color {
+        match (self, team) {
+            // the player getting hurt should stand out
+            (NumberKind::Damage, Team::Player) => Color::rgb(1.0, 0.2, 0.2),
+            (NumberKind::Damage, Team::Enemy) => Color::WHITE,
+            (NumberKind::ArmorPiercing, _) => Color::rgb(0.8, 0.3, 1.0),
+            (NumberKind::Heal, _) => Color::rgb(0.3, 1.0, 0.3),
+            (NumberKind::ShieldAbsorb, _) => Color::rgb(0.4, 0.8, 1.0),
+            (NumberKind::Status, _) => Color::rgb(1.0, 0.9, 0.2),
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default, Debug)]
+pub struct FloatingNumber {
+    pub timer: Timer,
+}
+
+/// Everything that happened to one entity this frame
+#[derive(Default)]
+struct Changes {
+    amounts: HashMap<NumberKind, u32>,
+    effects: Vec<Effect>,
+}
+
+impl Changes {
+    fn add(&mut self, kind: NumberKind, amount: u32) {
+        if amount > 0 {
+            *self.amounts.entry(kind).or_default() += amount;
+        }
+    }
+
+    /// The text of each number to show, in a fixed order so they stack the same way every time
+    fn texts(&self) -> Vec<(NumberKind, String)> {
+        let mut texts = Vec::new();
+        for kind in [
+            NumberKind::Damage,
+            NumberKind::ArmorPiercing,
+            NumberKind::ShieldAbsorb,
+            NumberKind::Heal,
+        ] {
+            if let Some(amount) = self.amounts.get(&kind) {
+                let sign = if kind == NumberKind::Heal { '+' } else { '-' };
+                texts.push((kind, format!("{sign}{amount}")));
+            }
+        }
+        if !self.effects.is_empty() {
+            let names: Vec<_> = self
+                .effects
+                .iter()
+                .map(|effect| format!("{effect:?}"))
+                .collect();
+            texts.push((NumberKind::Status, names.join(" ")));
+        }
+        texts
+    }
+}
+
+/// Healing of one entity that hasn't been shown yet
+pub struct PendingHeal {
+    amount: u32,
+    since_shown: f32,
+}
+
+impl Default for PendingHeal {
+    fn default() -> Self {
+        // the first heal in a while is shown right away
+        Self {
+            amount: 0,
+            since_shown: HEAL_MERGE_WINDOW,
+        }
+    }
+}
+
+impl PendingHeal {
+    /// Add `healed` and `delta` seconds, returning the healing to show if it's time to show it
+    fn tick(&mut self, healed: u32, delta: f32) -> Option<u32> {
+        self.amount += healed;
+        self.since_shown += delta;
+        if self.amount == 0 || self.since_shown < HEAL_MERGE_WINDOW {
+            return None;
+        }
+        self.since_shown = 0.0;
+        Some(std::mem::take(&mut self.amount))
+    }
+
+    /// Whether this is back to how it started, and can be forgotten
+    fn idle(&self) -> bool {
+        self.amount == 0 && self.since_shown >= HEAL_MERGE_WINDOW
+    }
+}
+
+pub fn spawn_floating_numbers(
+    mut commands: Commands,
+    mut ev_dealt: EventReader<DamageDealt>,
+    mut query: Query<(Entity, &mut Health, &GlobalTransform)>,
+    player: Query<&Transform, With<Player>>,
+    font: Res<GameFont>,
+    time: Res<Time>,
+    mut pending: Local<HashMap<Entity, PendingHeal>>,
+) {
+    // facing the camera right away, rather than once the billboards are next rotated
+    let rotation = player
+        .get_single()
+        .map_or(Quat::IDENTITY, |player| billboard_rotation(player.rotation));
+    let mut changes: HashMap<Entity, Changes> = HashMap::default();
+    for ev in ev_dealt.iter() {
+        let changes = changes.entry(ev.target).or_default();
+        let kind = match ev.damage_kind {
+            DamageKind::ArmorPiercing => NumberKind::ArmorPiercing,
+            DamageKind::Normal | DamageKind::OverTime => NumberKind::Damage,
+        };
+        changes.add(kind, ev.amount - ev.absorbed);
+        changes.add(NumberKind::ShieldAbsorb, ev.absorbed);
+        for effect in &ev.effects {
+            if !changes.effects.contains(effect) {
+                changes.effects.push(*effect);
+            }
+        }
+    }
+
+    pending.retain(|entity, _| query.contains(*entity));
+    for (entity, mut health, transform) in &mut query {
+        // only borrowed mutably when there's something to take, so health isn't marked as
+        // changed every frame
+        let healed = if health.healed() > 0 {
+            health.take_healed()
+        } else {
+            0
+        };
+        if healed > 0 || pending.contains_key(&entity) {
+            let heal = pending.entry(entity).or_default();
+            if let Some(amount) = heal.tick(healed, time.delta_seconds()) {
+                changes
+                    .entry(entity)
+                    .or_default()
+                    .add(NumberKind::Heal, amount);
+            }
+            if heal.idle() {
+                pending.remove(&entity);
+            }
+        }
+        let Some(changes) = changes.get(&entity) else {
+            continue;
+        };
+
+        let position = transform.translation();
+        for (i, (kind, text)) in changes.texts().into_iter().enumerate() {
+            // numbers of the same frame are stacked instead of drawn over each other
+            let height = NUMBER_HEIGHT + i as f32 * FONT_SIZE * TEXT_SCALE;
+            commands.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        text,
+                        TextStyle {
+                            font: font.0.clone(),
+                            font_size: FONT_SIZE,
+                            color: kind.color(&health.team),
+                        },
+                    ),
+                    transform: Transform::from_translation(position + Vec3::Z * height)
+                        .with_rotation(rotation)
+                        .with_scale(Vec3::splat(TEXT_SCALE)),
+                    ..default()
+                },
+                FloatingNumber {
+                    timer: Timer::from_seconds(NUMBER_LIFETIME, TimerMode::Once),
+                },
+                BillboardSprite,
+                Name::new("Floating Number"),
+            ));
+        }
+    }
+}
+
+pub fn rise_floating_numbers(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut FloatingNumber, &mut Transform, &mut Text)>,
+    time: Res<Time>,
+) {
+    for (entity, mut number, mut transform, mut text) in &mut query {
+        number.timer.tick(time.delta());
+        transform.translation.z += NUMBER_RISE_SPEED * time.delta_seconds();
+        for section in &mut text.sections {
+            section.style.color.set_a(number.timer.percent_left());
+        }
+        if number.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn healing_is_merged_until_the_window_passes() {
+        let mut heal = PendingHeal::default();
+        assert_eq!(heal.tick(5, 0.0), Some(5));
+        assert_eq!(heal.tick(1, 0.2), None);
+        assert_eq!(heal.tick(1, 0.2), None);
+        assert_eq!(heal.tick(0, 0.2), Some(2));
+        assert!(!heal.idle());
+        assert_eq!(heal.tick(0, HEAL_MERGE_WINDOW), None);
+        assert!(heal.idle());
+    }
+
+    #[derive(Resource, Default)]
+    struct ChangedHealth(usize);
+
+    fn count_changed_health(query: Query<(), Changed<Health>>, mut changed: ResMut<ChangedHealth>) {
+        changed.0 += query.iter().count();
+    }
+
+    #[test]
+    fn health_is_only_changed_by_healing() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<DamageDealt>()
+            .insert_resource(GameFont(Handle::default()))
+            .init_resource::<ChangedHealth>()
+            .add_system(spawn_floating_numbers)
+            .add_system(count_changed_health.after(spawn_floating_numbers));
+        let entity = app
+            .world
+            .spawn((Health::new(100, Team::Enemy), GlobalTransform::default()))
+            .id();
+        app.update();
+        app.world.resource_mut::<ChangedHealth>().0 = 0;
+
+        app.update();
+        assert_eq!(app.world.resource::<ChangedHealth>().0, 0);
+
+        let mut health = app.world.get_mut::<Health>(entity).unwrap();
+        health.inflict_damage(10);
+        health.heal(5);
+        app.update();
+        assert_eq!(app.world.get::<Health>(entity).unwrap().healed(), 0);
+    }
+}
@@ -0,0 +1,18 @@
+// the font all text is drawn with.
+//
+// bevy doesn't come with a font, text without one isn't drawn at all. every plugin that draws
+// text initializes `GameFont`, so whichever is added first loads it.
+
+use bevy::prelude::*;
+
+/// DejaVu Sans, see the license next to it
+pub const FONT_PATH: &str = "fonts/DejaVuSans.ttf";
+
+#[derive(Resource, Clone, Debug)]
+pub struct GameFont(pub Handle<Font>);
+
+impl FromWorld for GameFont {
+    fn from_world(world: &mut World) -> Self {
+        Self(world.resource::<AssetServer>().load(FONT_PATH))
+    }
+}
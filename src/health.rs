@@ -1,44 +1,116 @@
 use bevy::{prelude::Component, prelude::*, reflect::TypeUuid};
-use serde;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::bullet::Team;
-use crate::enemy::drop_table::DropTable;
+use crate::damage::apply_damage;
+use crate::shandle::SHandleLoad;
+use crate::simulation::SimulationSet;
+use crate::validate::{Problem, Validate};
 
 pub struct HealthPlugin;
 
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(despawn_dead)
-            .register_type::<Health>()
-            .add_event::<DeathEvent>();
+        app.add_systems(
+            (mark_dead, tick_health.before(apply_damage))
+                .in_set(SimulationSet::Damage)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .register_type::<Health>()
+        .add_event::<DeathEvent>();
     }
 }
 
-pub struct DeathEvent(pub Entity);
+pub struct DeathEvent {
+    pub entity: Entity,
+    /// whoever damaged the entity last
+    pub killer: Option<Entity>,
+}
 
-pub fn despawn_dead(
-    mut commands: Commands,
-    mut query: Query<(Entity, &mut Health)>,
-    mut ev_death: EventWriter<DeathEvent>,
-) {
+pub fn mark_dead(mut query: Query<(Entity, &mut Health)>, mut ev_death: EventWriter<DeathEvent>) {
     for (entity, mut health) in &mut query {
         if health.current == 0 && !health.dead {
-            // commands.entity(entity).despawn_recursive();
-            ev_death.send(DeathEvent(entity));
+            // whatever handles the death decides what happens to the entity
+            ev_death.send(DeathEvent {
+                entity,
+                killer: health.last_attacker,
+            });
             health.dead = true;
         }
     }
 }
 
-#[derive(Component, Reflect, Deserialize, TypeUuid, Clone, Debug, FromReflect)]
+/// Regenerate health, decay shields and count down invulnerability
+pub fn tick_health(mut query: Query<&mut Health>, fixed_time: Res<FixedTime>) {
+    for mut health in &mut query {
+        health.tick(fixed_time.period.as_secs_f32());
+    }
+}
+
+/// Health of anything that can be hit. In files everything but `max`, `current` and `team` is
+/// optional:
+/// ```ron
+/// health: Health (
+///     max: 200,
+///     current: 200,
+///     team: Enemy,
+///     defense: 5,
+///     // 4 health per second once it hasn't been hit for 3 seconds
+///     regen: 4.0,
+///     regen_delay: 3.0,
+///     // 50 shield that's gone after 10 seconds
+///     shield: 50,
+///     shield_decay: 5.0,
+///     // can't be hit again for half a second after a hit
+///     invulnerability: 0.5,
+/// ),
+/// ```
+#[derive(
+    Component, Reflect, Deserialize, Serialize, TypeUuid, Clone, Debug, FromReflect, SHandleLoad,
+)]
 #[uuid = "c2036e7e-c764-11ed-afa1-0242ac120002"]
 pub struct Health {
     max: u32,
     current: u32,
     pub team: Team,
-    #[serde(skip_deserializing)]
+    /// flat reduction to every hit that isn't armor piercing
+    #[serde(default)]
+    pub defense: u32,
+    /// health per second
+    #[serde(default)]
+    pub regen: f32,
+    /// seconds without taking damage before regen starts
+    #[serde(default)]
+    pub regen_delay: f32,
+    /// absorbs damage before health does
+    #[serde(default)]
+    shield: u32,
+    /// shield lost per second
+    #[serde(default)]
+    pub shield_decay: f32,
+    /// seconds after a hit that no other hit can land
+    #[serde(default)]
+    pub invulnerability: f32,
+    #[serde(skip)]
     dead: bool,
+    /// seconds since the last damage
+    #[serde(skip)]
+    since_damage: f32,
+    /// seconds of invulnerability left
+    #[serde(skip)]
+    invulnerable_for: f32,
+    /// regen and decay that don't add up to a whole point yet
+    #[serde(skip)]
+    regen_progress: f32,
+    #[serde(skip)]
+    decay_progress: f32,
+    /// health gained since it was last taken, for showing heal numbers
+    #[serde(skip)]
+    healed: u32,
+    /// whoever damaged this last, for kill credit
+    #[serde(skip)]
+    #[shandle(skip)]
+    pub last_attacker: Option<Entity>,
 }
 
 #[allow(dead_code)]
@@ -48,23 +120,249 @@ impl Health {
             max: health,
             current: health,
             team,
+            defense: 0,
+            regen: 0.0,
+            regen_delay: 0.0,
+            shield: 0,
+            shield_decay: 0.0,
+            invulnerability: 0.0,
             dead: false,
+            since_damage: 0.0,
+            invulnerable_for: 0.0,
+            regen_progress: 0.0,
+            decay_progress: 0.0,
+            healed: 0,
+            last_attacker: None,
         }
     }
 
-    pub fn inflict_damage(&mut self, value: u32) {
-        self.current = self.current.saturating_sub(value);
+    /// Take damage, the shield absorbs as much of it as it can. Returns how much it absorbed
+    pub fn inflict_damage(&mut self, value: u32) -> u32 {
+        let absorbed = value.min(self.shield);
+        self.shield -= absorbed;
+        self.current = self.current.saturating_sub(value - absorbed);
+        self.since_damage = 0.0;
+        self.regen_progress = 0.0;
+        absorbed
+    }
+
+    /// Start the invulnerability after a hit
+    pub fn start_invulnerability(&mut self) {
+        self.invulnerable_for = self.invulnerability;
+    }
+
+    /// Whether this was hit too recently to be hit again
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable_for > 0.0
+    }
+
+    pub fn shield(&self) -> u32 {
+        self.shield
+    }
+
+    pub fn add_shield(&mut self, value: u32) {
+        self.shield += value;
+        self.decay_progress = 0.0;
+    }
+
+    /// Whether regen has started, having gone `regen_delay` seconds without damage
+    pub fn out_of_combat(&self) -> bool {
+        self.since_damage >= self.regen_delay
+    }
+
+    /// Advance regen, shield decay and invulnerability by `delta` seconds
+    pub fn tick(&mut self, delta: f32) {
+        self.invulnerable_for = (self.invulnerable_for - delta).max(0.0);
+
+        if self.shield > 0 {
+            self.decay_progress += self.shield_decay * delta;
+            let decay = self.decay_progress.floor();
+            self.decay_progress -= decay;
+            self.shield = self.shield.saturating_sub(decay as u32);
+        } else {
+            self.decay_progress = 0.0;
+        }
+
+        self.since_damage += delta;
+        if self.dead || !self.out_of_combat() || self.current >= self.max {
+            self.regen_progress = 0.0;
+            return;
+        }
+        self.regen_progress += self.regen * delta;
+        let regen = self.regen_progress.floor();
+        self.regen_progress -= regen;
+        self.heal(regen as u32);
     }
 
     pub fn heal(&mut self, value: u32) {
+        let before = self.current;
         self.current += value;
 
         if self.current > self.max {
             self.current = self.max;
         }
+        self.healed += self.current - before;
+    }
+
+    /// Health gained since it was last taken
+    pub fn healed(&self) -> u32 {
+        self.healed
+    }
+
+    /// Health gained since the last call
+    pub fn take_healed(&mut self) -> u32 {
+        std::mem::take(&mut self.healed)
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.dead
+    }
+
+    /// Bring back to full health with no shield, regen or invulnerability in progress
+    pub fn revive(&mut self) {
+        self.current = self.max;
+        self.shield = 0;
+        self.dead = false;
+        self.since_damage = 0.0;
+        self.invulnerable_for = 0.0;
+        self.regen_progress = 0.0;
+        self.decay_progress = 0.0;
+        self.healed = 0;
+        self.last_attacker = None;
     }
 
     pub fn frac(&self) -> f32 {
         self.current as f32 / self.max as f32
     }
+
+    /// Take the stats of a reloaded definition, keeping the current health fraction
+    pub fn reload(&mut self, options: &Health) {
+        let frac = self.frac();
+        self.max = options.max;
+        self.current = (frac * self.max as f32).round() as u32;
+        if !self.dead && self.current == 0 {
+            self.current = 1;
+        }
+        self.team = options.team.clone();
+        self.defense = options.defense;
+        self.regen = options.regen;
+        self.regen_delay = options.regen_delay;
+        self.shield_decay = options.shield_decay;
+        self.invulnerability = options.invulnerability;
+    }
+}
+
+impl Validate for Health {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        if self.current > self.max {
+            problems.push(Problem::new(
+                format!(
+                    "current health {} is more than max health {}",
+                    self.current, self.max
+                ),
+                "current",
+            ));
+        }
+        for (value, name) in [
+            (self.regen, "regen"),
+            (self.regen_delay, "regen_delay"),
+            (self.shield_decay, "shield_decay"),
+            (self.invulnerability, "invulnerability"),
+        ] {
+            if value < 0.0 {
+                problems.push(Problem::new(format!("{name} {value} is negative"), name));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::damage::{DamageDealt, DamageEvent, DamageKind};
+
+    #[test]
+    fn the_shield_absorbs_damage_before_health() {
+        let mut health = Health::new(100, Team::Enemy);
+        health.add_shield(30);
+        assert_eq!(health.inflict_damage(20), 20);
+        assert_eq!((health.shield(), health.current), (10, 100));
+        assert_eq!(health.inflict_damage(25), 10);
+        assert_eq!((health.shield(), health.current), (0, 85));
+    }
+
+    #[test]
+    fn hits_reset_the_regen_delay() {
+        let mut health = Health::new(100, Team::Enemy);
+        health.regen = 10.0;
+        health.regen_delay = 2.0;
+        health.inflict_damage(50);
+        health.tick(1.5);
+        health.inflict_damage(10);
+        health.tick(1.5);
+        assert!(!health.out_of_combat());
+        assert_eq!(health.current, 40);
+        health.tick(1.0);
+        assert!(health.out_of_combat());
+        assert_eq!(health.current, 50);
+    }
+
+    #[test]
+    fn shields_decay_to_nothing() {
+        let mut health = Health::new(100, Team::Enemy);
+        health.shield_decay = 4.0;
+        health.add_shield(3);
+        health.tick(0.5);
+        assert_eq!(health.shield(), 1);
+        // more decay than is left
+        health.tick(2.0);
+        assert_eq!(health.shield(), 0);
+        health.tick(1.0);
+        assert_eq!(health.shield(), 0);
+        assert_eq!(health.current, 100);
+    }
+
+    #[test]
+    fn reloading_keeps_the_shield() {
+        let mut health = Health::new(100, Team::Enemy);
+        health.add_shield(20);
+        health.inflict_damage(70);
+        let mut options = Health::new(200, Team::Enemy);
+        options.shield = 50;
+        health.reload(&options);
+        assert_eq!(health.shield(), 0);
+
+        let mut health = Health::new(100, Team::Enemy);
+        health.add_shield(20);
+        health.reload(&options);
+        assert_eq!((health.shield(), health.current), (20, 200));
+    }
+
+    #[test]
+    fn invulnerability_only_stops_hits() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<DamageEvent>()
+            .add_event::<DamageDealt>()
+            .add_system(apply_damage);
+        let mut health = Health::new(100, Team::Enemy);
+        health.invulnerability = 0.5;
+        let target = app.world.spawn(health).id();
+        let hit = |app: &mut App, damage_kind| {
+            app.world.send_event(DamageEvent {
+                target,
+                source: None,
+                amount: 10,
+                damage_kind,
+                effects: Vec::new(),
+            });
+            app.update();
+            app.world.get::<Health>(target).unwrap().current
+        };
+
+        assert_eq!(hit(&mut app, DamageKind::Normal), 90);
+        assert_eq!(hit(&mut app, DamageKind::Normal), 90);
+        assert_eq!(hit(&mut app, DamageKind::ArmorPiercing), 90);
+        assert_eq!(hit(&mut app, DamageKind::OverTime), 80);
+    }
 }
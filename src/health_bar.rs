@@ -0,0 +1,375 @@
+// health bars.
+//
+// enemies get a small billboarded bar under them once they're first hit, which goes away when
+// they die. bosses get a big bar at the top of the screen instead, with their name, the phase
+// their ai is in and a marker wherever a phase ends. boss bars follow hot reloads: an enemy that
+// stops being a boss loses its bar, and the bar is rebuilt when the phases end somewhere else.
+
+use bevy::{prelude::*, sprite::Anchor, utils::HashSet};
+
+use crate::{
+    billboard_sprite::BillboardSprite,
+    damage::DamageDealt,
+    enemy::{Ai, EnemyOptions},
+    font::GameFont,
+    health::Health,
+    simulation::interpolate_transforms,
+};
+
+/// size of the bars over enemies
+pub const BAR_SIZE: Vec2 = Vec2::new(0.8, 0.08);
+/// how far under an enemy's origin its bar is
+pub const BAR_OFFSET: f32 = 0.15;
+
+const BAR_BACKGROUND: Color = Color::rgb(0.15, 0.15, 0.15);
+const BAR_FILL: Color = Color::rgb(0.8, 0.1, 0.1);
+
+pub struct HealthBarPlugin;
+
+impl Plugin for HealthBarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameFont>()
+            .add_startup_system(spawn_boss_bar_container)
+            .add_system(spawn_health_bars)
+            .add_system(update_health_bars.after(interpolate_transforms))
+            .add_system(spawn_boss_bars)
+            .add_system(update_boss_bars);
+    }
+}
+
+/// Marks an enemy that already has a bar over it
+#[derive(Component)]
+pub struct ShowsHealthBar;
+
+/// A bar over an enemy
+#[derive(Component)]
+pub struct HealthBar {
+    pub owner: Entity,
+    /// the part that shrinks as the owner loses health
+    pub fill: Entity,
+}
+
+/// Where boss bars go, at the top of the screen
+#[derive(Component)]
+pub struct BossBarContainer;
+
+/// A big bar for a boss
+#[derive(Component)]
+pub struct BossBar {
+    pub boss: Entity,
+    pub label: Entity,
+    pub fill: Entity,
+    /// where the markers are, the bar is rebuilt when the boss' ai changes them
+    pub thresholds: Vec<f32>,
+}
+
+type Boss<'a> = (
+    Entity,
+    &'a Ai,
+    &'a Health,
+    &'a Handle<EnemyOptions>,
+    Option<&'a Name>,
+);
+
+pub fn spawn_health_bars(
+    mut commands: Commands,
+    mut ev_dealt: EventReader<DamageDealt>,
+    query: Query<&Handle<EnemyOptions>, Without<ShowsHealthBar>>,
+    assets: Res<Assets<EnemyOptions>>,
+) {
+    // the same enemy can be hit more than once before the marker is inserted
+    let mut spawned = Vec::new();
+    for ev in ev_dealt.iter() {
+        if ev.amount == 0 || spawned.contains(&ev.target) {
+            continue;
+        }
+        let Ok(handle) = query.get(ev.target) else {
+            continue;
+        };
+        if assets.get(handle).is_some_and(|options| options.boss) {
+            continue;
+        }
+        commands.entity(ev.target).insert(ShowsHealthBar);
+        spawned.push(ev.target);
+
+        let fill = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: BAR_FILL,
+                        custom_size: Some(BAR_SIZE),
+                        anchor: Anchor::CenterLeft,
+                        ..default()
+                    },
+                    // slightly in front of the background
+                    transform: Transform::from_xyz(-BAR_SIZE.x / 2.0, 0.001, 0.0),
+                    ..default()
+                },
+                Name::new("Health Bar Fill"),
+            ))
+            .id();
+        commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: BAR_BACKGROUND,
+                        custom_size: Some(BAR_SIZE),
+                        ..default()
+                    },
+                    ..default()
+                },
+                BillboardSprite,
+                HealthBar {
+                    owner: ev.target,
+                    fill,
+                },
+                Name::new("Health Bar"),
+            ))
+            .add_child(fill);
+    }
+}
+
+pub fn update_health_bars(
+    mut commands: Commands,
+    mut bars: Query<(Entity, &HealthBar, &mut Transform)>,
+    owners: Query<(&Transform, &Health), Without<HealthBar>>,
+    mut fills: Query<&mut Transform, (Without<HealthBar>, Without<Health>)>,
+) {
+    for (entity, bar, mut transform) in &mut bars {
+        match owners.get(bar.owner) {
+            Ok((owner_transform, health)) if !health.is_dead() => {
+                transform.translation = owner_transform.translation - Vec3::Z * BAR_OFFSET;
+                if let Ok(mut fill) = fills.get_mut(bar.fill) {
+                    fill.scale.x = health.frac();
+                }
+            }
+            _ => commands.entity(entity).despawn_recursive(),
+        }
+    }
+}
+
+fn spawn_boss_bar_container(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.0),
+                    left: Val::Percent(20.0),
+                    ..default()
+                },
+                size: Size::width(Val::Percent(60.0)),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            ..default()
+        },
+        BossBarContainer,
+        Name::new("Boss Bars"),
+    ));
+}
+
+fn is_boss(health: &Health, handle: &Handle<EnemyOptions>, assets: &Assets<EnemyOptions>) -> bool {
+    !health.is_dead() && assets.get(handle).is_some_and(|options| options.boss)
+}
+
+/// Give every living boss a bar, and take it away from the dead and from enemies that were
+/// reloaded into not being bosses
+pub fn spawn_boss_bars(
+    mut commands: Commands,
+    bosses: Query<Boss>,
+    bars: Query<(Entity, &BossBar)>,
+    container: Query<Entity, With<BossBarContainer>>,
+    assets: Res<Assets<EnemyOptions>>,
+    font: Res<GameFont>,
+) {
+    let Ok(container) = container.get_single() else {
+        return;
+    };
+    let mut shown = HashSet::new();
+    for (entity, bar) in &bars {
+        // an enemy switching between inline and shared ai loses its ai for a moment too
+        let up_to_date = bosses
+            .get(bar.boss)
+            .is_ok_and(|(_, ai, health, handle, _)| {
+                is_boss(health, handle, &assets) && ai.health_thresholds() == bar.thresholds
+            });
+        if up_to_date {
+            shown.insert(bar.boss);
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for (boss, ai, health, handle, name) in &bosses {
+        if shown.contains(&boss) || !is_boss(health, handle, &assets) {
+            continue;
+        }
+        let thresholds = ai.health_thresholds();
+
+        let label = commands
+            .spawn(TextBundle::from_section(
+                name.map_or("Boss", Name::as_str),
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ))
+            .id();
+        let fill = commands
+            .spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    ..default()
+                },
+                background_color: BAR_FILL.into(),
+                ..default()
+            })
+            .id();
+        let bar = commands
+            .spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Px(16.0)),
+                    ..default()
+                },
+                background_color: BAR_BACKGROUND.into(),
+                ..default()
+            })
+            .add_child(fill)
+            .with_children(|bar| {
+                // the health each phase ends at
+                for threshold in &thresholds {
+                    bar.spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                left: Val::Percent(*threshold * 100.0),
+                                ..default()
+                            },
+                            size: Size::new(Val::Px(2.0), Val::Percent(100.0)),
+                            ..default()
+                        },
+                        background_color: Color::WHITE.into(),
+                        ..default()
+                    });
+                }
+            })
+            .id();
+        let root = commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        margin: UiRect::bottom(Val::Px(8.0)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                BossBar {
+                    boss,
+                    label,
+                    fill,
+                    thresholds,
+                },
+                Name::new("Boss Bar"),
+            ))
+            .push_children(&[label, bar])
+            .id();
+        commands.entity(container).add_child(root);
+    }
+}
+
+pub fn update_boss_bars(
+    bars: Query<&BossBar>,
+    bosses: Query<(&Health, &Ai, Option<&Name>)>,
+    mut labels: Query<&mut Text>,
+    mut fills: Query<&mut Style>,
+) {
+    for bar in &bars {
+        // spawn_boss_bars removes the bars of bosses that are gone
+        let Ok((health, ai, name)) = bosses.get(bar.boss) else {
+            continue;
+        };
+
+        let text = format!("{} - {}", name.map_or("Boss", Name::as_str), ai.current);
+        if let Ok(mut label) = labels.get_mut(bar.label) {
+            if label.sections[0].value != text {
+                label.sections[0].value = text;
+            }
+        }
+        if let Ok(mut fill) = fills.get_mut(bar.fill) {
+            fill.size.width = Val::Percent(health.frac() * 100.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bullet::Team,
+        collision::DEFAULT_HIT_RADIUS,
+        enemy::{drop_table::DropTable, test_ai, transitions::Transition, EnemyAi},
+        shandle::SHandle,
+        testing::{asset_app, MemoryAssetIo},
+    };
+
+    fn boss_bars(app: &mut App) -> Vec<Vec<f32>> {
+        app.update();
+        let mut query = app.world.query::<&BossBar>();
+        query
+            .iter(&app.world)
+            .map(|bar| bar.thresholds.clone())
+            .collect()
+    }
+
+    #[test]
+    fn boss_bars_follow_reloads() {
+        let mut app = asset_app(MemoryAssetIo::default());
+        app.add_asset::<EnemyOptions>()
+            .insert_resource(GameFont(Handle::default()))
+            .add_startup_system(spawn_boss_bar_container)
+            .add_system(spawn_boss_bars);
+        let options = app
+            .world
+            .resource_mut::<Assets<EnemyOptions>>()
+            .add(EnemyOptions {
+                health: Health::new(100, Team::Enemy),
+                sprite: SHandle::Serialized("boss.png".into()),
+                ai: EnemyAi::Inline(test_ai()),
+                drop_table: DropTable { drops: Vec::new() },
+                hit_radius: DEFAULT_HIT_RADIUS,
+                on_death: Vec::new(),
+                boss: true,
+            });
+        let boss = app
+            .world
+            .spawn((options.clone(), Health::new(100, Team::Enemy), test_ai()))
+            .id();
+        assert_eq!(boss_bars(&mut app), vec![vec![0.5]]);
+        // nothing changed
+        assert_eq!(boss_bars(&mut app), vec![vec![0.5]]);
+
+        let set_boss = |app: &mut App, boss| {
+            let mut assets = app.world.resource_mut::<Assets<EnemyOptions>>();
+            assets.get_mut(&options).unwrap().boss = boss;
+        };
+        set_boss(&mut app, false);
+        assert!(boss_bars(&mut app).is_empty());
+        set_boss(&mut app, true);
+        assert_eq!(boss_bars(&mut app), vec![vec![0.5]]);
+
+        // a shared ai file being reloaded changes the ai in place
+        let mut ai = app.world.get_mut::<Ai>(boss).unwrap();
+        ai.phases.get_mut("2").unwrap().transitions =
+            vec![(Transition::HealthLessThan(0.25), "Start".into())];
+        assert_eq!(boss_bars(&mut app), vec![vec![0.25, 0.5]]);
+
+        // switching between inline and shared ai removes it until the shared one is loaded
+        app.world.entity_mut(boss).remove::<Ai>();
+        assert!(boss_bars(&mut app).is_empty());
+        app.world.entity_mut(boss).insert(test_ai());
+        assert_eq!(boss_bars(&mut app), vec![vec![0.5]]);
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 
 use crate::{
-    billboard_sprite::{BillboardSprite, BillboardSpriteBundle, SPRITE8},
+    billboard_sprite::{BillboardSprite, SPRITE8},
     player::Player,
     shandle::SHandle,
 };
@@ -27,6 +27,33 @@ pub fn pickup_dropped_items(
     }
 }
 
+/// Apply sprite changes of modified item files to items lying on the ground
+pub fn reload_dropped_items(
+    mut ev_asset: EventReader<AssetEvent<Item>>,
+    assets: Res<Assets<Item>>,
+    mut query: Query<(&DroppedItem, &mut Handle<Image>)>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Modified { handle } = ev {
+            if let Some(item) = assets.get(handle) {
+                for (dropped_item, mut texture) in &mut query {
+                    if let SHandle::Loaded {
+                        handle: item_handle,
+                        ..
+                    } = &dropped_item.item
+                    {
+                        if item_handle == handle {
+                            if let Ok(sprite) = item.sprite.handle() {
+                                *texture = sprite;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub struct DroppedItemBundle {
     dropped_item: DroppedItem,
@@ -35,16 +62,18 @@ pub struct DroppedItemBundle {
 }
 
 impl DroppedItemBundle {
-    pub fn new(item: SHandle<Item>, translation: Vec3, assets: &Assets<Item>) -> Self {
-        Self {
-            dropped_item: DroppedItem { item: item.clone() },
+    /// `None` if the item or its sprite isn't loaded, since it couldn't be seen
+    pub fn new(item: SHandle<Item>, translation: Vec3, assets: &Assets<Item>) -> Option<Self> {
+        let texture = assets.get(&item.handle().ok()?)?.sprite.handle().ok()?;
+        Some(Self {
+            dropped_item: DroppedItem { item },
             sprite_bundle: SpriteBundle {
                 sprite: SPRITE8,
                 transform: Transform::from_translation(translation),
-                texture: assets.get(&item.unwrap()).unwrap().sprite.unwrap(),
+                texture,
                 ..default()
             },
             billboard_sprite: BillboardSprite,
-        }
+        })
     }
 }
@@ -1,4 +1,4 @@
-use bevy::{prelude::*, reflect};
+use bevy::prelude::*;
 
 use crate::shandle::SHandle;
 
@@ -9,8 +9,8 @@ pub struct Inventory {
     pub contents: [Option<SHandle<Item>>; 8],
 }
 
-impl Inventory {
-    pub fn new() -> Self {
+impl Default for Inventory {
+    fn default() -> Self {
         Self {
             contents: std::array::from_fn(|_| None),
         }
@@ -1,20 +1,17 @@
 use std::fmt::Debug;
 
-use bevy::{
-    asset::{LoadContext, LoadedAsset},
-    prelude::*,
-    reflect::TypeUuid,
-    render::texture::CompressedImageFormats,
-};
-use serde::{de::DeserializeOwned, Deserialize};
+use bevy::{prelude::*, reflect::TypeUuid};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
 
 use crate::{
-    bullet::{Bullet, BulletOptions},
+    bullet::BulletOptions,
     loader,
-    shandle::{load_ron, load_sprite, store_ron, SHandle, SHandleLoad},
+    shandle::{SHandle, SHandleLoad},
+    status_effect::{validate_effects, Effect},
+    validate::{in_field, Problem, Validate},
 };
 
-#[derive(TypeUuid, Debug, Reflect, FromReflect, Clone, Deserialize)]
+#[derive(TypeUuid, Debug, Reflect, FromReflect, Clone, Deserialize, Serialize, SHandleLoad)]
 #[uuid = "0635cefa-f22c-4347-8166-38821647325a"]
 pub struct Item {
     pub name: String,
@@ -22,43 +19,90 @@ pub struct Item {
     pub item_type: ItemType,
 }
 
-#[derive(TypeUuid, Debug, Clone, Reflect, FromReflect, Deserialize)]
+#[derive(TypeUuid, Debug, Clone, Reflect, FromReflect, Deserialize, Serialize, SHandleLoad)]
 #[uuid = "0635cefa-f22c-4347-8166-38821647325b"]
 pub enum ItemType {
     Regular,
     Equipable(EquipableType),
+    /// used up to apply status effects to whoever uses it, with their duration in seconds:
+    /// `Consumable ( effects: [(Healing, 5.0), (Speedy, 5.0)] )`
+    Consumable {
+        effects: Vec<(Effect, f32)>,
+    },
 }
 
-#[derive(TypeUuid, Debug, Clone, Reflect, FromReflect, Deserialize)]
+#[derive(TypeUuid, Debug, Clone, Reflect, FromReflect, Deserialize, Serialize, SHandleLoad)]
 #[uuid = "0635cefa-f22c-4347-8166-38821647325c"]
 pub enum EquipableType {
-    // path to bullet
-    Weapon(SHandle<BulletOptions>),
+    Weapon {
+        bullet: SHandle<BulletOptions>,
+        /// shots per second
+        #[serde(
+            default = "default_rate_of_fire",
+            deserialize_with = "deserialize_rate_of_fire"
+        )]
+        rate_of_fire: f32,
+    },
     Ability,
     Armor,
     Accessory,
 }
 
-// loader!(Item, ItemLoader, &["item"]);
+fn default_rate_of_fire() -> f32 {
+    4.0
+}
 
-#[derive(Default)]
-pub struct ItemLoader;
+// the time between shots is 1 / rate_of_fire, so anything else can't be fired
+fn deserialize_rate_of_fire<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let rate_of_fire = f32::deserialize(deserializer)?;
+    if rate_of_fire > 0.0 {
+        Ok(rate_of_fire)
+    } else {
+        Err(D::Error::custom(format!(
+            "rate_of_fire {rate_of_fire} must be more than 0"
+        )))
+    }
+}
 
-impl bevy::asset::AssetLoader for ItemLoader {
-    fn load<'a>(
-        &'a self,
-        _bytes: &'a [u8],
-        load_context: &'a mut bevy::asset::LoadContext,
-    ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
-        Box::pin(async move {
-            let mut shandle: SHandle<Item> =
-                SHandle::Serialized(load_context.path().to_string_lossy().to_string());
-            shandle.shandle_load(load_context, true).await?;
+impl Validate for Item {
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        if let ItemType::Consumable { effects } = &self.item_type {
+            in_field("item_type", problems, |problems| {
+                in_field("effects", problems, |problems| {
+                    validate_effects(effects, problems)
+                })
+            });
+        }
+    }
+}
+
+loader!(Item, ItemLoader, &["item"]);
 
-            Ok(())
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shandle::deserialize_ron;
+
+    fn weapon(rate_of_fire: &str) -> String {
+        format!(
+            r#"Item(
+                name: "Gun",
+                sprite: Serialized("gun.png"),
+                item_type: Equipable(Weapon(
+                    bullet: Serialized("gun.bullet"),
+                    rate_of_fire: {rate_of_fire},
+                )),
+            )"#
+        )
     }
-    fn extensions(&self) -> &[&str] {
-        &["item"]
+
+    #[test]
+    fn rate_of_fire_must_be_positive() {
+        assert!(deserialize_ron::<Item>(weapon("2.5").as_bytes()).is_ok());
+        for rate_of_fire in ["0.0", "-1.0"] {
+            let err = deserialize_ron::<Item>(weapon(rate_of_fire).as_bytes()).unwrap_err();
+            assert!(err.message.contains("must be more than 0"), "{err}");
+            assert_eq!(err.position.map(|(line, _)| line), Some(6));
+        }
     }
 }
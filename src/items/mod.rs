@@ -4,8 +4,10 @@ pub mod item;
 
 use bevy::prelude::*;
 
+use crate::{player::use_consumables, simulation::SimulationSet};
+
 use self::{
-    dropped_item::{pickup_dropped_items, DroppedItem},
+    dropped_item::{pickup_dropped_items, reload_dropped_items, DroppedItem},
     inventory::Inventory,
     item::{Item, ItemLoader},
 };
@@ -19,6 +21,12 @@ impl Plugin for ItemsPlugin {
             .init_asset_loader::<ItemLoader>()
             .register_type::<DroppedItem>()
             .register_type::<Inventory>()
-            .add_system(pickup_dropped_items);
+            .add_system(
+                pickup_dropped_items
+                    .after(use_consumables)
+                    .in_set(SimulationSet::Player)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(reload_dropped_items);
     }
 }
@@ -0,0 +1,62 @@
+pub mod billboard_sprite;
+pub mod bullet;
+pub mod bullet_pool;
+pub mod camera;
+pub mod collision;
+pub mod content;
+pub mod content_error;
+pub mod damage;
+pub mod enemy;
+pub mod floating_numbers;
+pub mod font;
+pub mod health;
+pub mod health_bar;
+pub mod items;
+pub mod player;
+pub mod player_death;
+pub mod shandle;
+pub mod simulation;
+pub mod status_effect;
+pub mod template;
+#[cfg(test)]
+pub mod testing;
+pub mod validate;
+
+// macro to implement an asset loader for a ron asset, loading all nested shandles
+// TODO: move somewhere lol
+#[macro_export]
+macro_rules! loader {
+    ($T:ident, $LOADER:ident, $extensions:expr) => {
+        pub struct $LOADER {
+            reporter: $crate::content_error::ContentReporter,
+        }
+
+        impl bevy::prelude::FromWorld for $LOADER {
+            fn from_world(world: &mut bevy::prelude::World) -> Self {
+                Self {
+                    reporter: world
+                        .get_resource_or_insert_with(
+                            $crate::content_error::ContentReporter::default,
+                        )
+                        .clone(),
+                }
+            }
+        }
+
+        impl bevy::asset::AssetLoader for $LOADER {
+            fn load<'a>(
+                &'a self,
+                bytes: &'a [u8],
+                load_context: &'a mut bevy::asset::LoadContext,
+            ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+                Box::pin(async move {
+                    $crate::shandle::load_root::<$T>(bytes, load_context, &self.reporter).await
+                })
+            }
+
+            fn extensions(&self) -> &[&str] {
+                $extensions
+            }
+        }
+    };
+}
@@ -1,21 +1,22 @@
-mod billboard_sprite;
-mod bullet;
-mod camera;
-mod enemy;
-mod health;
-mod items;
-mod player;
-pub mod shandle;
-
 use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use billboard_sprite::BillboardSpritePlugin;
-use bullet::BulletPlugin;
-use camera::DiagonalProjectionPlugin;
-use enemy::{Ai, EnemyBundle, EnemyPlugin};
-use health::HealthPlugin;
-use items::ItemsPlugin;
-use player::PlayerPlugin;
+use btll::{
+    billboard_sprite::BillboardSpritePlugin,
+    bullet::BulletPlugin,
+    camera::DiagonalProjectionPlugin,
+    content::ContentPlugin,
+    content_error::ContentErrorPlugin,
+    damage::DamagePlugin,
+    enemy::{EnemyBundle, EnemyPlugin},
+    floating_numbers::FloatingNumbersPlugin,
+    health::HealthPlugin,
+    health_bar::HealthBarPlugin,
+    items::ItemsPlugin,
+    player::PlayerPlugin,
+    player_death::{PlayerDeathPlugin, SpawnPoint},
+    simulation::SimulationPlugin,
+    status_effect::StatusEffectPlugin,
+};
 fn main() {
     App::new()
         .add_plugins(
@@ -27,56 +28,39 @@ fn main() {
                 }),
         )
         .add_plugin(WorldInspectorPlugin::new())
+        .add_plugin(ContentErrorPlugin)
+        .add_plugin(ContentPlugin)
+        .add_plugin(SimulationPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(PlayerDeathPlugin)
         .add_plugin(BillboardSpritePlugin)
         .add_plugin(DiagonalProjectionPlugin)
         .add_plugin(HealthPlugin)
+        .add_plugin(DamagePlugin)
+        .add_plugin(StatusEffectPlugin)
         .add_plugin(EnemyPlugin)
         .add_plugin(BulletPlugin)
         .add_plugin(ItemsPlugin)
+        .add_plugin(FloatingNumbersPlugin)
+        .add_plugin(HealthBarPlugin)
         .add_startup_system(startup)
         .run();
 }
 
 fn startup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let handle = asset_server.load::<Ai, _>("test.ai");
-    Box::leak(Box::new(handle.clone()));
-    // Box::leak(Box::new(asset_server.load::<ItemOptions, _>("test.item")));
-
     commands.spawn((
         EnemyBundle {
             options: asset_server.load("test.enemy"),
             spatial_bundle: default(),
         },
         Name::new("TEST ENTITY"),
-        handle,
     ));
-}
-
-// macro to implement an asset loader
-// TODO: move somewhere lol
-#[macro_export]
-macro_rules! loader {
-    ($T:ident, $LOADER:ident, $extensions:expr) => {
-        #[derive(Default)]
-        pub struct $LOADER;
-
-        impl bevy::asset::AssetLoader for $LOADER {
-            fn load<'a>(
-                &'a self,
-                bytes: &'a [u8],
-                load_context: &'a mut bevy::asset::LoadContext,
-            ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
-                Box::pin(async move {
-                    let custom_asset = ron::de::from_bytes::<$T>(bytes)?;
-                    load_context.set_default_asset(bevy::asset::LoadedAsset::new(custom_asset));
-                    Ok(())
-                })
-            }
 
-            fn extensions(&self) -> &[&str] {
-                $extensions
-            }
-        }
-    };
+    for position in [Vec3::new(0.0, -8.0, 0.0), Vec3::new(8.0, 8.0, 0.0)] {
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position)),
+            SpawnPoint,
+            Name::new("Spawn Point"),
+        ));
+    }
 }
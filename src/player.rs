@@ -1,40 +1,93 @@
-use bevy::prelude::*;
+use bevy::{input::InputSystem, prelude::*, window::PrimaryWindow};
 use std::f32::consts::PI;
 
 use crate::{
     billboard_sprite::{BillboardSprite, BillboardSpriteBundle},
     bullet::{BulletBundle, BulletOptions, Team},
-    camera::DiagonalCameraBundle,
+    bullet_pool::BulletPool,
+    camera::{cursor_to_ground, DiagonalCameraBundle, DiagonalProjection},
+    content::ContentRegistry,
     health::Health,
     items::{
         inventory::Inventory,
         item::{EquipableType, Item, ItemType},
     },
+    player_death::{AlivePlayer, Dead, PlayerStats},
     shandle::SHandle,
+    simulation::{Interpolated, SimulationSet},
+    status_effect::StatusEffects,
 };
 
 #[derive(Component)]
 pub struct Player;
 
+/// Weapon state of the player
+#[derive(Component, Default)]
+pub struct Shooting {
+    /// time until the weapon can fire again
+    pub cooldown: Timer,
+    /// keep firing without holding the mouse button, toggled with `I`
+    pub auto_fire: bool,
+    /// volleys fired so far, to turn spiral patterns
+    pub volley: u32,
+}
+
+/// What the player wants to do, read from the keyboard and mouse every frame and used up by the
+/// simulation, so gameplay only depends on these inputs and not on when they were pressed.
+///
+/// Presses are kept until a tick handles them, in case a frame has no tick.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+pub struct PlayerInput {
+    /// direction to move in, relative to the camera
+    pub movement: Vec2,
+    /// which way to turn the camera, `1.0` is counterclockwise
+    pub rotation: f32,
+    pub reset_rotation: bool,
+    /// the fire button is held
+    pub fire: bool,
+    pub toggle_auto_fire: bool,
+    /// the point on the ground under the cursor
+    pub aim: Option<Vec2>,
+    /// inventory slot to use
+    pub use_slot: Option<usize>,
+}
+
+/// How fast the player is moving, in units per second
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+pub struct Velocity(pub Vec2);
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(spawn_player)
-            .add_system(player_shooting)
+            .add_system(
+                read_player_input
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem),
+            )
+            .add_systems(
+                (player_movement, player_shooting, use_consumables)
+                    .chain()
+                    .in_set(SimulationSet::Player)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
             .add_system(inv_debug)
-            .add_system(player_movement);
+            .register_type::<PlayerInput>()
+            .register_type::<Velocity>();
     }
 }
 
 pub fn inv_debug(
     keyboard_input: Res<Input<KeyCode>>,
     mut query: Query<&mut Inventory, With<Player>>,
+    registry: Res<ContentRegistry>,
     asset_server: Res<AssetServer>,
-    mut assets: ResMut<Assets<Item>>,
 ) {
     if keyboard_input.just_pressed(KeyCode::B) {
-        query.single_mut().contents[0] = Some(SHandle::Loaded(asset_server.load("weapon.item")));
+        if let Some(handle) = registry.get::<Item>("item:test_weapon") {
+            query.single_mut().contents[0] = SHandle::from_handle(handle, &asset_server);
+        }
     }
 
     if keyboard_input.just_pressed(KeyCode::C) {
@@ -47,13 +100,24 @@ pub fn inv_debug(
 }
 
 pub fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mut health = Health::new(100, Team::Player);
+    health.regen = 2.0;
+    health.regen_delay = 5.0;
+    health.invulnerability = 0.5;
+
     commands
         .spawn((
             SpatialBundle::default(),
             Player,
-            Health::new(100, Team::Player),
+            Velocity::default(),
+            PlayerInput::default(),
+            PlayerStats::default(),
+            Interpolated::default(),
+            Shooting::default(),
+            StatusEffects::default(),
+            health,
             Name::new("Player"),
-            Inventory::new(),
+            Inventory::default(),
         ))
         .with_children(|parent| {
             // camera
@@ -77,19 +141,29 @@ pub fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
         });
 }
 
-pub fn player_movement(
+pub fn read_player_input(
     keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-    mut query: Query<&mut Transform, With<Player>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut query: Query<&mut PlayerInput, Without<Dead>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&GlobalTransform, &DiagonalProjection)>,
 ) {
-    const SPEED: f32 = 5.0;
-    const ROTATION_SPEED: f32 = 4.0;
-    let mut movement = Vec2::ZERO;
+    const SLOT_KEYS: [KeyCode; 8] = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+        KeyCode::Key7,
+        KeyCode::Key8,
+    ];
 
-    let mut rotation = 0.0;
-
-    let mut reset_rotation = false;
+    let Ok(mut input) = query.get_single_mut() else {
+        return;
+    };
 
+    let mut movement = Vec2::ZERO;
     // movement
     if keyboard_input.pressed(KeyCode::W) {
         movement.y += 1.0;
@@ -103,6 +177,9 @@ pub fn player_movement(
     if keyboard_input.pressed(KeyCode::D) {
         movement.x += 1.0;
     }
+    input.movement = movement.normalize_or_zero();
+
+    let mut rotation = 0.0;
     // rotation
     if keyboard_input.pressed(KeyCode::Q) {
         rotation += 1.0;
@@ -110,24 +187,53 @@ pub fn player_movement(
     if keyboard_input.pressed(KeyCode::E) {
         rotation -= 1.0;
     }
+    input.rotation = rotation;
     // reset rotation
-    if keyboard_input.just_pressed(KeyCode::Z) {
-        reset_rotation = true;
+    input.reset_rotation |= keyboard_input.just_pressed(KeyCode::Z);
+
+    input.fire = mouse_input.pressed(MouseButton::Left);
+    input.toggle_auto_fire |= keyboard_input.just_pressed(KeyCode::I);
+    if let (Ok(window), Ok((camera_transform, projection))) =
+        (windows.get_single(), camera.get_single())
+    {
+        input.aim = window.cursor_position().and_then(|cursor| {
+            let window_size = Vec2::new(window.width(), window.height());
+            cursor_to_ground(cursor, window_size, camera_transform, projection)
+        });
     }
 
-    movement = movement.normalize_or_zero();
-    movement *= time.delta_seconds();
-    movement *= SPEED;
+    if let Some(slot) = SLOT_KEYS
+        .into_iter()
+        .position(|key| keyboard_input.just_pressed(key))
+    {
+        input.use_slot = Some(slot);
+    }
+}
 
-    rotation *= time.raw_delta_seconds();
-    rotation *= ROTATION_SPEED;
+pub fn player_movement(
+    fixed_time: Res<FixedTime>,
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut PlayerInput,
+            &StatusEffects,
+        ),
+        AlivePlayer,
+    >,
+) {
+    const SPEED: f32 = 5.0;
+    const ROTATION_SPEED: f32 = 4.0;
+    let delta = fixed_time.period.as_secs_f32();
 
-    for mut transform in &mut query {
+    for (mut transform, mut velocity, mut input, effects) in &mut query {
+        let movement = effects.movement(input.movement) * SPEED;
         let movement = transform.right() * movement.x + transform.up() * movement.y;
-        transform.translation += movement;
-        transform.rotate_z(rotation);
+        velocity.0 = movement.truncate();
+        transform.translation += movement * delta;
+        transform.rotate_z(input.rotation * ROTATION_SPEED * delta);
 
-        if reset_rotation {
+        if std::mem::take(&mut input.reset_rotation) {
             let new_rotation = -transform.rotation.to_euler(EulerRot::XYZ).2;
             transform.rotate_z(new_rotation);
         }
@@ -135,30 +241,104 @@ pub fn player_movement(
 }
 
 pub fn player_shooting(
-    keyboard_input: Res<Input<KeyCode>>,
-    _time: Res<Time>,
+    fixed_time: Res<FixedTime>,
     mut commands: Commands,
-    // mut query: Query<&mut Shooting, With<Player>>,
-    query: Query<(&Inventory, &Transform), With<Player>>,
-    asset_server: Res<AssetServer>,
-    mut assets: ResMut<Assets<Item>>,
+    mut pool: ResMut<BulletPool>,
+    mut query: Query<
+        (
+            Entity,
+            &Inventory,
+            &Transform,
+            &mut Shooting,
+            &mut PlayerInput,
+            &StatusEffects,
+        ),
+        AlivePlayer,
+    >,
+    assets: Res<Assets<Item>>,
     bullets: Res<Assets<BulletOptions>>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        let (inventory, transform) = query.single();
-        // inv 0 is something
-        if let Some(handle) = &inventory.contents[0] {
-            let item = assets.get_mut(&handle.unwrap()).unwrap();
-            if let ItemType::Equipable(equipable) = &mut item.item_type {
-                if let EquipableType::Weapon(bullet_handle) = equipable {
-                    commands.spawn(BulletBundle::new(
-                        bullets.get(&bullet_handle.unwrap()).unwrap().clone(),
-                        0.0,
-                        transform.translation.truncate(),
-                        &asset_server,
-                    ));
-                }
-            }
-        }
+    let Ok((player, inventory, transform, mut shooting, mut input, effects)) =
+        query.get_single_mut()
+    else {
+        return;
+    };
+
+    if std::mem::take(&mut input.toggle_auto_fire) {
+        shooting.auto_fire = !shooting.auto_fire;
+    }
+    shooting.cooldown.tick(fixed_time.period);
+    if !(input.fire || shooting.auto_fire) || !shooting.cooldown.finished() || !effects.can_shoot()
+    {
+        return;
+    }
+
+    // inv 0 is something
+    let Some(item) = inventory.contents[0]
+        .as_ref()
+        .and_then(|handle| handle.handle().ok())
+        .and_then(|handle| assets.get(&handle))
+    else {
+        return;
+    };
+    let ItemType::Equipable(EquipableType::Weapon {
+        bullet,
+        rate_of_fire,
+    }) = &item.item_type
+    else {
+        return;
+    };
+    let Ok(bullet_handle) = bullet.handle() else {
+        return;
+    };
+    let Some(bullet_options) = bullets.get(&bullet_handle) else {
+        return;
+    };
+    let Some(aim) = input.aim else {
+        return;
+    };
+
+    let position = transform.translation.truncate();
+    let offset = aim - position;
+    pool.spawn_batch(
+        &mut commands,
+        BulletBundle::volley(
+            bullet_handle,
+            bullet_options,
+            player,
+            offset.y.atan2(offset.x),
+            shooting.volley,
+            position,
+        ),
+    );
+    shooting.volley = shooting.volley.wrapping_add(1);
+    let rate_of_fire = rate_of_fire * effects.rate_of_fire();
+    shooting.cooldown = Timer::from_seconds(1.0 / rate_of_fire, TimerMode::Once);
+}
+
+/// Use the consumable in the inventory slot picked with the number keys, applying its effects
+pub fn use_consumables(
+    mut query: Query<(&mut Inventory, &mut StatusEffects, &mut PlayerInput), AlivePlayer>,
+    assets: Res<Assets<Item>>,
+) {
+    let Ok((mut inventory, mut effects, mut input)) = query.get_single_mut() else {
+        return;
+    };
+    let Some(slot) = input.use_slot.take() else {
+        return;
+    };
+    let Some(item) = inventory.contents[slot]
+        .as_ref()
+        .and_then(|handle| handle.handle().ok())
+        .and_then(|handle| assets.get(&handle))
+    else {
+        return;
+    };
+    if let ItemType::Consumable {
+        effects: item_effects,
+    } = &item.item_type
+    {
+        effects.apply_all(item_effects, None);
+        inventory.contents[slot] = None;
     }
 }
@@ -0,0 +1,546 @@
+// what happens when the player dies.
+//
+// the player entity is kept around: it's hidden, stops taking input and can't be hit until it
+// respawns. a gravestone marks where it died, and a summary of the life is shown until it
+// respawns at the safest spawn point, with or without its items depending on `DeathPenalty`.
+
+use bevy::prelude::*;
+
+use crate::{
+    billboard_sprite::BillboardSpriteBundle,
+    damage::{DamageDealt, Invulnerable},
+    enemy::{death::handle_enemy_death, Ai},
+    font::GameFont,
+    health::{mark_dead, DeathEvent, Health},
+    items::inventory::Inventory,
+    player::{Player, PlayerInput, Shooting},
+    simulation::{Interpolated, SimulationSet},
+    status_effect::StatusEffects,
+};
+
+/// seconds between dying and respawning
+pub const RESPAWN_DELAY: f32 = 5.0;
+
+/// Query filter for the player while they're alive
+pub type AlivePlayer = (With<Player>, Without<Dead>);
+
+/// Everything about the player that's reset when they respawn
+type Respawned<'a> = (
+    Entity,
+    &'a mut Dead,
+    &'a mut Transform,
+    &'a mut Interpolated,
+    &'a mut Health,
+    &'a mut Inventory,
+    &'a mut StatusEffects,
+    &'a mut Shooting,
+    &'a mut PlayerStats,
+    &'a mut Visibility,
+);
+
+pub struct PlayerDeathPlugin;
+
+impl Plugin for PlayerDeathPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DeathPenalty>()
+            .init_resource::<GameFont>()
+            .init_resource::<DeathSummary>()
+            .add_systems(
+                (
+                    track_player_stats.after(mark_dead),
+                    handle_player_death
+                        .after(mark_dead)
+                        .after(track_player_stats),
+                    respawn_player
+                        .after(handle_player_death)
+                        .before(handle_enemy_death),
+                )
+                    .in_set(SimulationSet::Deaths)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_startup_system(spawn_death_summary_overlay)
+            .add_system(update_death_summary_overlay)
+            .register_type::<Dead>()
+            .register_type::<Gravestone>()
+            .register_type::<SpawnPoint>()
+            .register_type::<PlayerStats>();
+    }
+}
+
+/// What the player loses when they die
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeathPenalty {
+    /// start over with a fresh character
+    #[default]
+    Permadeath,
+    /// respawn with the same items
+    KeepItems,
+}
+
+/// Where the player can respawn, the one furthest from any enemy is picked
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+pub struct SpawnPoint;
+
+/// On the player while they're waiting to respawn
+#[derive(Component, Reflect, Default, Debug)]
+pub struct Dead {
+    pub respawn: Timer,
+}
+
+/// Marks where someone died
+#[derive(Component, Reflect, Default, Debug)]
+pub struct Gravestone {
+    pub name: String,
+    pub cause: String,
+}
+
+/// How the player's current life is going
+#[derive(Component, Reflect, Default, Clone, Debug)]
+pub struct PlayerStats {
+    pub time_alive: f32,
+    pub damage_dealt: u64,
+    pub kills: u32,
+}
+
+/// The last death of the player, shown until they respawn
+#[derive(Resource, Default, Debug)]
+pub struct DeathSummary(pub Option<DeathReport>);
+
+#[derive(Clone, Debug)]
+pub struct DeathReport {
+    pub name: String,
+    pub cause: String,
+    pub stats: PlayerStats,
+}
+
+impl DeathReport {
+    pub fn text(&self, respawn_in: f32) -> String {
+        format!(
+            "{} died\nkilled by {}\n\nsurvived {:.0} seconds\ndealt {} damage\nkilled {} enemies\n\nrespawning in {:.0}",
+            self.name,
+            self.cause,
+            self.stats.time_alive,
+            self.stats.damage_dealt,
+            self.stats.kills,
+            respawn_in.ceil(),
+        )
+    }
+}
+
+pub fn track_player_stats(
+    mut ev_dealt: EventReader<DamageDealt>,
+    mut ev_death: EventReader<DeathEvent>,
+    mut query: Query<(Entity, &mut PlayerStats, Option<&Dead>), With<Player>>,
+    fixed_time: Res<FixedTime>,
+) {
+    let Ok((player, mut stats, dead)) = query.get_single_mut() else {
+        return;
+    };
+    if dead.is_none() {
+        stats.time_alive += fixed_time.period.as_secs_f32();
+    }
+    for ev in ev_dealt.iter() {
+        if ev.source == Some(player) && ev.target != player {
+            stats.damage_dealt += ev.amount as u64;
+        }
+    }
+    for ev in ev_death.iter() {
+        if ev.killer == Some(player) && ev.entity != player {
+            stats.kills += 1;
+        }
+    }
+}
+
+pub fn handle_player_death(
+    mut commands: Commands,
+    mut ev_death: EventReader<DeathEvent>,
+    mut query: Query<
+        (
+            &Transform,
+            &Name,
+            &PlayerStats,
+            &mut PlayerInput,
+            &mut Visibility,
+        ),
+        With<Player>,
+    >,
+    names: Query<&Name>,
+    mut summary: ResMut<DeathSummary>,
+    asset_server: Res<AssetServer>,
+) {
+    for ev in ev_death.iter() {
+        let Ok((transform, name, stats, mut input, mut visibility)) = query.get_mut(ev.entity)
+        else {
+            continue;
+        };
+
+        let cause = ev
+            .killer
+            .and_then(|killer| names.get(killer).ok())
+            .map_or_else(|| "something unknown".to_string(), ToString::to_string);
+
+        commands.entity(ev.entity).insert((
+            Dead {
+                respawn: Timer::from_seconds(RESPAWN_DELAY, TimerMode::Once),
+            },
+            Invulnerable,
+        ));
+        *input = PlayerInput::default();
+        *visibility = Visibility::Hidden;
+
+        let mut gravestone =
+            BillboardSpriteBundle::new_anchored(asset_server.load("gravestone.png"));
+        gravestone.transform.translation = transform.translation;
+        commands.spawn((
+            gravestone,
+            Gravestone {
+                name: name.to_string(),
+                cause: cause.clone(),
+            },
+            Name::new(format!("Gravestone of {name}")),
+        ));
+
+        summary.0 = Some(DeathReport {
+            name: name.to_string(),
+            cause,
+            stats: stats.clone(),
+        });
+    }
+}
+
+pub fn respawn_player(
+    mut commands: Commands,
+    mut query: Query<Respawned, With<Player>>,
+    spawn_points: Query<&Transform, (With<SpawnPoint>, Without<Player>)>,
+    enemies: Query<&Transform, (With<Ai>, Without<Player>)>,
+    penalty: Res<DeathPenalty>,
+    mut summary: ResMut<DeathSummary>,
+    fixed_time: Res<FixedTime>,
+) {
+    let Ok((
+        player,
+        mut dead,
+        mut transform,
+        mut interpolated,
+        mut health,
+        mut inventory,
+        mut effects,
+        mut shooting,
+        mut stats,
+        mut visibility,
+    )) = query.get_single_mut()
+    else {
+        return;
+    };
+    dead.respawn.tick(fixed_time.period);
+    if !dead.respawn.finished() {
+        return;
+    }
+
+    // the spawn point with the most room from the closest enemy
+    let safety = |point: Vec3| {
+        enemies
+            .iter()
+            .map(|enemy| enemy.translation.truncate().distance(point.truncate()))
+            .fold(f32::INFINITY, f32::min)
+    };
+    let spawn = spawn_points
+        .iter()
+        .map(|point| point.translation)
+        .max_by(|a, b| safety(*a).total_cmp(&safety(*b)))
+        .unwrap_or_default();
+
+    transform.translation = spawn.truncate().extend(transform.translation.z);
+    interpolated.snap(&transform);
+    health.revive();
+    *effects = StatusEffects::default();
+    *shooting = Shooting::default();
+    *stats = PlayerStats::default();
+    if *penalty == DeathPenalty::Permadeath {
+        *inventory = Inventory::default();
+    }
+    *visibility = Visibility::Inherited;
+    commands.entity(player).remove::<(Dead, Invulnerable)>();
+    summary.0 = None;
+}
+
+#[derive(Component)]
+pub struct DeathSummaryOverlay;
+
+fn spawn_death_summary_overlay(mut commands: Commands, font: Res<GameFont>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font.0.clone(),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(30.0),
+                left: Val::Percent(40.0),
+                ..default()
+            },
+            ..default()
+        }),
+        DeathSummaryOverlay,
+        Name::new("Death Summary Overlay"),
+    ));
+}
+
+fn update_death_summary_overlay(
+    summary: Res<DeathSummary>,
+    dead: Query<&Dead, With<Player>>,
+    mut query: Query<&mut Text, With<DeathSummaryOverlay>>,
+) {
+    let text = match (&summary.0, dead.get_single()) {
+        (Some(report), Ok(dead)) => report.text(dead.respawn.remaining_secs()),
+        _ => String::new(),
+    };
+    for mut overlay in &mut query {
+        if overlay.sections[0].value != text {
+            overlay.sections[0].value = text.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::schedule::Schedule;
+
+    use super::*;
+    use crate::{
+        bullet::{BulletOptions, BulletPlugin, Team},
+        bullet_pool::BulletPool,
+        damage::{DamageEvent, DamageKind, DamagePlugin},
+        enemy::{behaviors::Behavior, test_ai, EnemyPlugin, Phase},
+        health::HealthPlugin,
+        items::item::Item,
+        player::{player_movement, read_player_input, Velocity},
+        shandle::SHandle,
+        simulation::{SimulationPlugin, TICK_RATE},
+        testing::{asset_app, MemoryAssetIo},
+    };
+
+    fn app() -> App {
+        let mut app = asset_app(MemoryAssetIo::default());
+        app.add_plugin(SimulationPlugin)
+            .add_plugin(HealthPlugin)
+            .add_plugin(DamagePlugin)
+            .add_plugin(BulletPlugin)
+            .add_plugin(EnemyPlugin)
+            .add_plugin(PlayerDeathPlugin)
+            .add_asset::<Item>()
+            .add_system(
+                player_movement
+                    .in_set(SimulationSet::Player)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+        app
+    }
+
+    fn spawn_player(app: &mut App) -> Entity {
+        let transform = Transform::from_xyz(0.0, 0.0, 0.5);
+        app.world
+            .spawn((
+                SpatialBundle::from_transform(transform),
+                Player,
+                Velocity::default(),
+                PlayerInput::default(),
+                PlayerStats::default(),
+                Interpolated::new(&transform),
+                Shooting::default(),
+                StatusEffects::default(),
+                Health::new(100, Team::Player),
+                Name::new("Player"),
+                Inventory::default(),
+            ))
+            .id()
+    }
+
+    fn kill(app: &mut App, player: Entity, killer: Option<Entity>) {
+        app.world.send_event(DamageEvent {
+            target: player,
+            source: killer,
+            amount: 1000,
+            damage_kind: DamageKind::ArmorPiercing,
+            effects: Vec::new(),
+        });
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert!(app.world.get::<Dead>(player).is_some());
+    }
+
+    fn wait_to_respawn(app: &mut App) {
+        for _ in 0..(RESPAWN_DELAY * TICK_RATE) as usize + 1 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+    }
+
+    #[test]
+    fn dead_players_stop_taking_input() {
+        let mut app = app();
+        app.insert_resource(Input::<KeyCode>::default())
+            .insert_resource(Input::<MouseButton>::default());
+        let player = spawn_player(&mut app);
+        let mut read_input = Schedule::new();
+        read_input.add_system(read_player_input);
+
+        let mut input = app.world.get_mut::<PlayerInput>(player).unwrap();
+        input.movement = Vec2::X;
+        input.fire = true;
+        kill(&mut app, player, None);
+
+        let input = app.world.get::<PlayerInput>(player).unwrap();
+        assert_eq!(input.movement, Vec2::ZERO);
+        assert!(!input.fire);
+        assert_eq!(
+            app.world.get::<Visibility>(player),
+            Some(&Visibility::Hidden)
+        );
+
+        // neither new presses nor input that's already there move them
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::W);
+        read_input.run(&mut app.world);
+        assert_eq!(
+            app.world.get::<PlayerInput>(player).unwrap().movement,
+            Vec2::ZERO
+        );
+        app.world.get_mut::<PlayerInput>(player).unwrap().movement = Vec2::X;
+        let position = app.world.get::<Transform>(player).unwrap().translation;
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert_eq!(
+            app.world.get::<Transform>(player).unwrap().translation,
+            position
+        );
+    }
+
+    #[test]
+    fn gravestones_record_the_killer() {
+        let mut app = app();
+        let player = spawn_player(&mut app);
+        let killer = app.world.spawn(Name::new("Goblin")).id();
+        kill(&mut app, player, Some(killer));
+
+        let mut gravestones = app.world.query::<&Gravestone>();
+        let gravestone = gravestones.single(&app.world);
+        assert_eq!(gravestone.name, "Player");
+        assert_eq!(gravestone.cause, "Goblin");
+        let report = app.world.resource::<DeathSummary>().0.clone().unwrap();
+        assert_eq!(report.cause, "Goblin");
+    }
+
+    #[test]
+    fn respawns_at_the_spawn_point_furthest_from_enemies() {
+        let mut app = app();
+        let player = spawn_player(&mut app);
+        for x in [0.0, 10.0, -10.0] {
+            app.world
+                .spawn((Transform::from_xyz(x, 0.0, 0.0), SpawnPoint));
+        }
+        // (-10, 0) is 9 away from the closest enemy, the others only 1
+        for x in [9.0, -1.0] {
+            app.world
+                .spawn((Transform::from_xyz(x, 0.0, 0.0), test_ai()));
+        }
+        kill(&mut app, player, None);
+        wait_to_respawn(&mut app);
+
+        assert!(app.world.get::<Dead>(player).is_none());
+        assert!(!app.world.get::<Health>(player).unwrap().is_dead());
+        assert_eq!(
+            app.world.get::<Transform>(player).unwrap().translation,
+            Vec3::new(-10.0, 0.0, 0.5)
+        );
+        assert_eq!(
+            app.world.get::<Visibility>(player),
+            Some(&Visibility::Inherited)
+        );
+        assert!(app.world.resource::<DeathSummary>().0.is_none());
+    }
+
+    #[test]
+    fn the_death_penalty_decides_whether_items_are_kept() {
+        for (penalty, kept) in [
+            (DeathPenalty::Permadeath, false),
+            (DeathPenalty::KeepItems, true),
+        ] {
+            let mut app = app();
+            app.insert_resource(penalty);
+            let player = spawn_player(&mut app);
+            app.world.get_mut::<Inventory>(player).unwrap().contents[0] =
+                Some(SHandle::Serialized("sword.item".into()));
+            kill(&mut app, player, None);
+            wait_to_respawn(&mut app);
+
+            assert!(app.world.get::<Dead>(player).is_none());
+            let inventory = app.world.get::<Inventory>(player).unwrap();
+            assert_eq!(inventory.contents[0].is_some(), kept, "{penalty:?}");
+        }
+    }
+
+    #[test]
+    fn enemies_ignore_dead_players() {
+        let mut app = app();
+        let bullet = app
+            .world
+            .resource_mut::<Assets<BulletOptions>>()
+            .add(BulletOptions {
+                sprite: SHandle::Loaded {
+                    path: "bullet.png".into(),
+                    handle: Handle::default(),
+                },
+                ..default()
+            });
+        let player = spawn_player(&mut app);
+        let enemy = app
+            .world
+            .spawn((
+                SpatialBundle::from_transform(Transform::from_xyz(0.0, 5.0, 0.0)),
+                Ai {
+                    phases: [(
+                        "Start".into(),
+                        Phase {
+                            behaviors: vec![
+                                Behavior::ChasePlayer { speed: 1.0 },
+                                Behavior::ShootAtPlayer {
+                                    bullet: SHandle::Loaded {
+                                        path: "bullet.bullet".into(),
+                                        handle: bullet,
+                                    },
+                                    interval: 0.05,
+                                    predictive: false,
+                                    inaccuracy: 0.0,
+                                    timer: default(),
+                                    volley: 0,
+                                },
+                            ],
+                            transitions: Vec::new(),
+                        },
+                    )]
+                    .into(),
+                    current: "Start".into(),
+                },
+            ))
+            .id();
+        kill(&mut app, player, None);
+        // the tick the player died in already had them as a target
+        let position = app.world.get::<Transform>(enemy).unwrap().translation;
+        let shots = app.world.resource::<BulletPool>().stats().spawned;
+
+        for _ in 0..10 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        assert_eq!(
+            app.world.get::<Transform>(enemy).unwrap().translation,
+            position
+        );
+        assert_eq!(app.world.resource::<BulletPool>().stats().spawned, shots);
+
+        // or no player at all
+        app.world.despawn(player);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+    }
+}
@@ -1,17 +1,21 @@
-use async_trait::async_trait;
 use bevy::{
-    asset::{LoadContext, LoadedAsset},
+    asset::{Asset, AssetPath, LoadContext, LoadedAsset},
     prelude::*,
     reflect::TypeUuid,
-    render::texture::CompressedImageFormats,
 };
-use serde::{de::DeserializeOwned, Deserialize};
-use std::{fmt::Debug, path::Path};
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    hash::Hash,
+    path::{Component as PathComponent, Path},
+};
+
+pub use btll_derive::SHandleLoad;
 
 use crate::{
-    bullet::BulletOptions,
-    enemy::{behaviors::Behavior, EnemyOptions},
-    items::item::{EquipableType, Item, ItemType},
+    content_error::{ContentError, ContentReporter},
+    template::TemplateChain,
 };
 
 // Serializable handle
@@ -19,195 +23,485 @@ use crate::{
 #[uuid = "57422828-c764-11ed-aca1-0242ac120002"]
 pub enum SHandle<T: bevy::asset::Asset + Reflect + Debug + FromReflect> {
     Serialized(String),
+    /// keeps the path as it was written so it can be serialized again
     #[serde(skip_deserializing)]
-    Loaded(Handle<T>),
+    Loaded {
+        path: String,
+        handle: Handle<T>,
+    },
 }
 
-// trait to eventually make a shandle load derive macro
-//
-#[async_trait]
-pub trait SHandleLoad
-where
-    Self: FromReflect + bevy::asset::Asset + Debug + DeserializeOwned,
-{
-    async fn shandle_load<'a>(
-        &mut self,
-        load_context: &mut LoadContext<'a>,
-        root: bool,
-    ) -> Result<(), bevy::asset::Error>;
-}
-
-#[async_trait]
-impl SHandleLoad for SHandle<Image> {
-    async fn shandle_load<'a>(
-        &mut self,
-        load_context: &mut LoadContext<'a>,
-        root: bool,
-    ) -> Result<(), bevy::asset::Error> {
-        let path = self.path();
-        let bytes = load_context.read_asset_bytes(path.clone()).await?;
-
-        let ext = Path::new(&path).extension().unwrap().to_str().unwrap();
-        let sprite = Image::from_buffer(
-            &bytes,
-            bevy::render::texture::ImageType::Extension(ext),
-            CompressedImageFormats::all(),
-            true,
-        )?;
-
-        let handle = load_context.set_labeled_asset(&path, LoadedAsset::new(sprite));
-        *self = SHandle::Loaded(handle);
-        Ok(())
+// both variants are written as `Serialized("path")` so a loaded asset can be saved again
+impl<T: bevy::asset::Asset + Reflect + Debug + FromReflect> Serialize for SHandle<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_variant("SHandle", 0, "Serialized", self.path())
     }
 }
 
-#[async_trait]
-impl SHandleLoad for SHandle<Item> {
-    async fn shandle_load<'a>(
-        &mut self,
-        load_context: &mut LoadContext<'a>,
-        root: bool,
-    ) -> Result<(), bevy::asset::Error> {
-        let mut asset = load_ron(self, load_context).await?;
-        asset.sprite.shandle_load(load_context, false).await?;
-        match &mut asset.item_type {
-            ItemType::Regular => {}
-            ItemType::Equipable(e) => match e {
-                EquipableType::Weapon(bullet_options_shandle) => {
-                    bullet_options_shandle
-                        .shandle_load(load_context, false)
-                        .await?;
-                }
-                _ => {}
-            },
+/// Turns the [`SHandle`]s of an asset file into handles while it is being loaded.
+///
+/// Every referenced path becomes the canonical handle the asset server uses for that path
+/// and is registered as a dependency, so each file is only loaded once no matter how many
+/// other files reference it.
+pub struct SHandleLoader<'a, 'b> {
+    load_context: &'a LoadContext<'b>,
+    reporter: &'a ContentReporter,
+    dependencies: Vec<AssetPath<'static>>,
+}
+
+impl<'a, 'b> SHandleLoader<'a, 'b> {
+    pub fn new(load_context: &'a LoadContext<'b>, reporter: &'a ContentReporter) -> Self {
+        Self {
+            load_context,
+            reporter,
+            dependencies: Vec::new(),
         }
-        store_ron(self, asset, load_context, root);
+    }
 
-        Ok(())
+    /// Get the handle for a path referenced from the file being loaded
+    pub fn load<T: Asset>(&mut self, path: &str) -> Result<Handle<T>, ContentError> {
+        let path = resolve_path(self.load_context.path(), path)?;
+        self.reporter
+            .reference(&asset_path_string(self.load_context.path()), &path);
+        let asset_path = AssetPath::new(path.into(), None);
+        let handle = self.load_context.get_handle(asset_path.get_id());
+        if !self.dependencies.contains(&asset_path) {
+            self.dependencies.push(asset_path);
+        }
+        Ok(handle)
+    }
+
+    pub fn into_dependencies(self) -> Vec<AssetPath<'static>> {
+        self.dependencies
     }
 }
 
-#[async_trait]
-impl SHandleLoad for SHandle<BulletOptions> {
-    async fn shandle_load<'a>(
-        &mut self,
-        load_context: &mut LoadContext<'a>,
-        root: bool,
-    ) -> Result<(), bevy::asset::Error> {
-        let mut asset = load_ron(self, load_context).await?;
-        asset.sprite.shandle_load(load_context, false).await?;
-        store_ron(self, asset, load_context, root);
+/// Loads every [`SHandle`] nested inside a value.
+///
+/// Implement it with `#[derive(SHandleLoad)]` on anything that ends up inside an asset,
+/// the derive walks all fields and recurses through containers.
+pub trait SHandleLoad {
+    fn shandle_load(&mut self, loader: &mut SHandleLoader) -> Result<(), ContentError>;
 
+    /// Collect the paths of all serialized handles, as written in the file
+    fn shandle_paths<'p>(&'p self, paths: &mut Vec<&'p str>);
+}
+
+impl<T: bevy::asset::Asset + Reflect + Debug + FromReflect> SHandleLoad for SHandle<T> {
+    fn shandle_load(&mut self, loader: &mut SHandleLoader) -> Result<(), ContentError> {
+        if let SHandle::Serialized(path) = self {
+            *self = SHandle::Loaded {
+                handle: loader.load(path)?,
+                path: std::mem::take(path),
+            };
+        }
         Ok(())
     }
+
+    fn shandle_paths<'p>(&'p self, paths: &mut Vec<&'p str>) {
+        if let SHandle::Serialized(path) = self {
+            paths.push(path);
+        }
+    }
 }
 
-#[async_trait]
-impl SHandleLoad for SHandle<EnemyOptions> {
-    async fn shandle_load<'a>(
-        &mut self,
-        load_context: &mut LoadContext<'a>,
-        root: bool,
-    ) -> Result<(), bevy::asset::Error> {
-        let mut asset = load_ron(self, load_context).await?;
-        asset.sprite.shandle_load(load_context, false).await?;
-
-        for (item_shandle, _) in &mut asset.drop_table.drops {
-            item_shandle.shandle_load(load_context, false).await?;
-        }
-
-        for (_name, phase) in &mut asset.ai.phases {
-            for b in &mut phase.behaviors {
-                match b {
-                    Behavior::ShootAtPlayer {
-                        bullet,
-                        interval,
-                        timer,
-                    } => {
-                        bullet.shandle_load(load_context, false).await?;
-                    }
-                    _ => {}
-                }
-            }
-            // item_shandle.shandle_load(load_context, false).await?;
+impl<T: SHandleLoad> SHandleLoad for Vec<T> {
+    fn shandle_load(&mut self, loader: &mut SHandleLoader) -> Result<(), ContentError> {
+        for value in self.iter_mut() {
+            value.shandle_load(loader)?;
         }
+        Ok(())
+    }
 
-        store_ron(self, asset, load_context, root);
+    fn shandle_paths<'p>(&'p self, paths: &mut Vec<&'p str>) {
+        for value in self.iter() {
+            value.shandle_paths(paths);
+        }
+    }
+}
 
+impl<T: SHandleLoad> SHandleLoad for Option<T> {
+    fn shandle_load(&mut self, loader: &mut SHandleLoader) -> Result<(), ContentError> {
+        if let Some(value) = self {
+            value.shandle_load(loader)?;
+        }
         Ok(())
     }
+
+    fn shandle_paths<'p>(&'p self, paths: &mut Vec<&'p str>) {
+        if let Some(value) = self {
+            value.shandle_paths(paths);
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: SHandleLoad> SHandleLoad for HashMap<K, V> {
+    fn shandle_load(&mut self, loader: &mut SHandleLoader) -> Result<(), ContentError> {
+        for value in self.values_mut() {
+            value.shandle_load(loader)?;
+        }
+        Ok(())
+    }
+
+    fn shandle_paths<'p>(&'p self, paths: &mut Vec<&'p str>) {
+        for value in self.values() {
+            value.shandle_paths(paths);
+        }
+    }
 }
 
-// more direct trait maybe in the future
-// impl Load for EnemyOptions {
-//      pub fn load(bytes, ctx) {
-//          let asset = from_bytes(bytes);
-//          // asset.sprite: SHandle<Image>
-//          asset.sprite.shandle_load(ctx);
-//          asset.drop_table.shandle_load(ctx);
-//      }
-// }
+macro_rules! impl_shandle_load_tuple {
+    ($($T:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($T: SHandleLoad),*> SHandleLoad for ($($T,)*) {
+            fn shandle_load(&mut self, loader: &mut SHandleLoader) -> Result<(), ContentError> {
+                let ($($T,)*) = self;
+                $($T.shandle_load(loader)?;)*
+                Ok(())
+            }
+
+            fn shandle_paths<'p>(&'p self, paths: &mut Vec<&'p str>) {
+                let ($($T,)*) = self;
+                $($T.shandle_paths(paths);)*
+            }
+        }
+    };
+}
+
+impl_shandle_load_tuple!(A);
+impl_shandle_load_tuple!(A, B);
+impl_shandle_load_tuple!(A, B, C);
+impl_shandle_load_tuple!(A, B, C, D);
+
+// types that can never contain a handle
+macro_rules! impl_shandle_load_leaf {
+    ($($T:ty),*) => {
+        $(
+            impl SHandleLoad for $T {
+                fn shandle_load(&mut self, _loader: &mut SHandleLoader) -> Result<(), ContentError> {
+                    Ok(())
+                }
+
+                fn shandle_paths<'p>(&'p self, _paths: &mut Vec<&'p str>) {}
+            }
+        )*
+    };
+}
+
+impl_shandle_load_leaf!(
+    bool, u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64, String, Timer
+);
 
 impl<T: bevy::asset::Asset + Reflect + Debug + FromReflect> SHandle<T> {
+    /// Wrap a handle that was loaded by the asset server, `None` if it wasn't loaded from a path
+    pub fn from_handle(handle: Handle<T>, asset_server: &AssetServer) -> Option<Self> {
+        let path = asset_server.get_handle_path(&handle)?;
+        Some(SHandle::Loaded {
+            path: asset_path_string(path.path()),
+            handle,
+        })
+    }
+
     pub fn load(&mut self, asset_server: &AssetServer) {
         if let SHandle::Serialized(path) = self {
-            *self = SHandle::Loaded(asset_server.load(path.clone()));
+            *self = SHandle::Loaded {
+                handle: asset_server.load(path.as_str()),
+                path: std::mem::take(path),
+            };
         }
     }
 
-    pub fn unwrap(&self) -> Handle<T> {
+    pub fn handle(&self) -> Result<Handle<T>, ContentError> {
         match self {
-            SHandle::Serialized(_) => panic!("SHandle not loaded!"),
-            SHandle::Loaded(handle) => handle.clone(),
+            SHandle::Serialized(path) => Err(ContentError::new(format!(
+                "handle to \"{path}\" has not been loaded"
+            ))),
+            SHandle::Loaded { handle, .. } => Ok(handle.clone()),
         }
     }
 
-    pub fn path(&self) -> String {
+    /// The path as it was written in the file
+    pub fn path(&self) -> &str {
         match self {
-            SHandle::Serialized(path) => path.clone(),
-            SHandle::Loaded(_) => panic!(),
+            SHandle::Serialized(path) | SHandle::Loaded { path, .. } => path,
         }
     }
 }
 
-pub async fn load_ron<'a, T: bevy::asset::Asset + Debug + FromReflect + DeserializeOwned>(
-    shandle: &mut SHandle<T>,
-    load_context: &mut LoadContext<'a>,
-) -> Result<T, bevy::asset::Error> {
-    let path = shandle.path();
-    let bytes = load_context.read_asset_bytes(path.clone()).await?;
-    let asset = ron::de::from_bytes::<T>(&bytes)?;
-    Ok(asset)
+/// Resolves a path referenced from the file at `base` into a path relative to the asset root.
+///
+/// - `./file` and `../file` are relative to the directory containing `base`
+/// - `/file` is explicitly relative to the asset root
+/// - anything else is relative to the asset root as well
+pub fn resolve_path(base: &Path, path: &str) -> Result<String, ContentError> {
+    let mut components: Vec<String> = Vec::new();
+    let relative = ["./", "../", ".\\", "..\\"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix));
+
+    if relative {
+        if let Some(dir) = base.parent() {
+            components.extend(dir.components().filter_map(|c| match c {
+                PathComponent::Normal(s) => Some(s.to_string_lossy().to_string()),
+                _ => None,
+            }));
+        }
+    }
+
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    return Err(ContentError::new(format!(
+                        "path \"{path}\" referenced from \"{}\" points outside of the asset folder",
+                        base.display()
+                    )));
+                }
+            }
+            part => components.push(part.to_string()),
+        }
+    }
+
+    Ok(components.join("/"))
+}
+
+/// Path of a file relative to the asset folder, as used in error chains
+pub fn asset_path_string(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
 }
 
-pub fn store_ron<'a, T: bevy::asset::Asset + Debug + FromReflect + DeserializeOwned>(
-    shandle: &mut SHandle<T>,
-    asset: T,
+/// Deserializes ron, recording where in the file it failed
+pub fn deserialize_ron<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ContentError> {
+    let mut deserializer = ron::Deserializer::from_bytes(bytes)?;
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|err| {
+        // `?` marks a part of the path that couldn't be named, like a field that didn't parse
+        let path = err.path().to_string();
+        let field = path.trim_end_matches(".?").trim_end_matches('?');
+        let mut error = ContentError::from(deserializer.span_error(err.into_inner()));
+        if !field.is_empty() && field != "." {
+            error.field = Some(field.to_string());
+        }
+        error
+    })?;
+    deserializer
+        .end()
+        .map_err(|err| deserializer.span_error(err))?;
+    Ok(value)
+}
+
+/// Serializes to pretty printed ron, the way content files are written
+pub fn serialize_ron<T: Serialize>(value: &T) -> Result<String, ContentError> {
+    let config = ron::ser::PrettyConfig::new()
+        .indentor("    ".to_string())
+        .struct_names(true);
+    Ok(ron::ser::to_string_pretty(value, config)?)
+}
+
+/// Deserializes a root asset, loads all of its nested handles and stores it as the default asset.
+///
+/// Errors are also sent to the [`ContentReporter`] so they show up in game.
+pub async fn load_root<'a, T>(
+    bytes: &[u8],
     load_context: &mut LoadContext<'a>,
-    root: bool,
-) {
-    if root {
-        load_context.set_default_asset(LoadedAsset::new(asset));
-    } else {
-        let handle = load_context.set_labeled_asset(&shandle.path(), LoadedAsset::new(asset));
-        *shandle = SHandle::Loaded(handle);
+    reporter: &ContentReporter,
+) -> Result<(), bevy::asset::Error>
+where
+    T: bevy::asset::Asset + Debug + FromReflect + DeserializeOwned + SHandleLoad,
+{
+    let file = asset_path_string(load_context.path());
+    match load_root_inner::<T>(bytes, load_context, reporter).await {
+        Ok(()) => {
+            reporter.loaded(&file);
+            Ok(())
+        }
+        Err(err) => {
+            let err = err.in_file(reporter.chain(&file));
+            reporter.error(err.clone());
+            Err(err.into())
+        }
     }
 }
 
-pub async fn load_sprite<'a>(
-    shandle: &mut SHandle<Image>,
+async fn load_root_inner<'a, T>(
+    bytes: &[u8],
     load_context: &mut LoadContext<'a>,
-) -> Result<(), bevy::asset::Error> {
-    let path = shandle.path();
-    let bytes = load_context.read_asset_bytes(path.clone()).await?;
-    let sprite = Image::from_buffer(
-        &bytes,
-        bevy::render::texture::ImageType::Extension("png"),
-        CompressedImageFormats::all(),
-        true,
-    )?;
-
-    let handle = load_context.set_labeled_asset(&path, LoadedAsset::new(sprite));
-    *shandle = SHandle::Loaded(handle);
+    reporter: &ContentReporter,
+) -> Result<(), ContentError>
+where
+    T: bevy::asset::Asset + Debug + FromReflect + DeserializeOwned + SHandleLoad,
+{
+    let mut templates = Vec::new();
+    let mut asset = match TemplateChain::new(load_context.path(), bytes)? {
+        Some(mut chain) => {
+            while let Some(parent) = chain.next_parent()? {
+                // also watches the template, reloading this file when it changes
+                let bytes = load_context
+                    .read_asset_bytes(&parent)
+                    .await
+                    .map_err(|err| {
+                        ContentError::new(err.to_string()).in_file(vec![parent.clone()])
+                    })?;
+                templates.push(AssetPath::new(parent.clone().into(), None));
+                chain.push_parent(parent, &bytes)?;
+            }
+            chain.deserialize::<T>()?
+        }
+        None => deserialize_ron::<T>(bytes)?,
+    };
+    let mut loader = SHandleLoader::new(load_context, reporter);
+    asset.shandle_load(&mut loader)?;
+    let mut dependencies = loader.into_dependencies();
+    for template in templates {
+        if !dependencies.contains(&template) {
+            dependencies.push(template);
+        }
+    }
+    load_context.set_default_asset(LoadedAsset::new(asset).with_dependencies(dependencies));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        loader,
+        testing::{asset_app, load, MemoryAssetIo},
+    };
+    use bevy::asset::LoadState;
+
+    #[derive(Deserialize, TypeUuid, Reflect, FromReflect, Debug, SHandleLoad)]
+    #[uuid = "0b8f6a5e-2d4c-4e59-8a51-7c2f1d9e3b60"]
+    struct Leaf;
+
+    #[derive(Deserialize, TypeUuid, Reflect, FromReflect, Debug, SHandleLoad)]
+    #[uuid = "5e1d7c3a-9b2f-4a86-b0e4-3f6c8d2a1e97"]
+    struct Refs {
+        refs: Vec<SHandle<Leaf>>,
+    }
+
+    loader!(Leaf, LeafLoader, &["leaf"]);
+    loader!(Refs, RefsLoader, &["refs"]);
+
+    fn resolve(base: &str, path: &str) -> Result<String, ContentError> {
+        resolve_path(Path::new(base), path)
+    }
+
+    #[test]
+    fn resolves_paths() {
+        assert_eq!(resolve("a/b/c.refs", "./d.leaf").unwrap(), "a/b/d.leaf");
+        assert_eq!(resolve("a/b/c.refs", "../d.leaf").unwrap(), "a/d.leaf");
+        assert_eq!(resolve("a/b/c.refs", "../../d.leaf").unwrap(), "d.leaf");
+        assert_eq!(
+            resolve("a/b/c.refs", "./e/../d.leaf").unwrap(),
+            "a/b/d.leaf"
+        );
+        assert_eq!(resolve("a/b/c.refs", "/a/d.leaf").unwrap(), "a/d.leaf");
+        assert_eq!(resolve("a/b/c.refs", "d.leaf").unwrap(), "d.leaf");
+        assert_eq!(resolve("c.refs", "./d.leaf").unwrap(), "d.leaf");
+        assert_eq!(resolve("a/c.refs", ".\\d.leaf").unwrap(), "a/d.leaf");
+    }
+
+    #[test]
+    fn paths_above_the_root_are_errors() {
+        assert!(resolve("a/b/c.refs", "../../../d.leaf").is_err());
+        assert!(resolve("c.refs", "../d.leaf").is_err());
+        assert!(resolve("a/c.refs", "/../d.leaf").is_err());
+        assert!(resolve("a/c.refs", "e/../../d.leaf").is_err());
+    }
+
+    fn app(files: &[(&str, &str)]) -> App {
+        let mut app = asset_app(MemoryAssetIo::new(files));
+        app.add_asset::<Leaf>()
+            .add_asset::<Refs>()
+            .init_asset_loader::<LeafLoader>()
+            .init_asset_loader::<RefsLoader>();
+        app
+    }
+
+    #[test]
+    fn loads_handles_relative_to_the_file() {
+        let mut app = app(&[
+            (
+                "a/b/c.refs",
+                r#"Refs(refs: [
+                    Serialized("./d.leaf"),
+                    Serialized("../e.leaf"),
+                    Serialized("/a/b/d.leaf"),
+                    Serialized("f.leaf"),
+                ])"#,
+            ),
+            ("a/b/d.leaf", "Leaf"),
+            ("a/e.leaf", "Leaf"),
+            ("f.leaf", "Leaf"),
+        ]);
+        let (handle, state) = load::<Refs>(&mut app, "a/b/c.refs");
+        assert_eq!(state, LoadState::Loaded);
+
+        let asset_server = app.world.resource::<AssetServer>();
+        let refs = app.world.resource::<Assets<Refs>>().get(&handle).unwrap();
+        let paths: Vec<_> = refs
+            .refs
+            .iter()
+            .map(|shandle| {
+                let handle = shandle.handle().unwrap();
+                asset_path_string(asset_server.get_handle_path(handle).unwrap().path())
+            })
+            .collect();
+        assert_eq!(paths, ["a/b/d.leaf", "a/e.leaf", "a/b/d.leaf", "f.leaf"]);
+        // the same file is the same handle, no matter how it was written
+        assert_eq!(
+            refs.refs[0].handle().unwrap(),
+            refs.refs[2].handle().unwrap()
+        );
+        // paths are kept as they were written
+        assert_eq!(refs.refs[1].path(), "../e.leaf");
+    }
+
+    #[test]
+    fn templates_are_watched_dependencies() {
+        let io = MemoryAssetIo::new(&[
+            ("a/c.refs", r#"Refs(extends: "/b.refs")"#),
+            (
+                "b.refs",
+                r#"Refs(extends: "./c.refs", refs: [Serialized("./d.leaf")])"#,
+            ),
+            ("c.refs", r#"Refs(refs: [])"#),
+            ("d.leaf", "Leaf"),
+        ]);
+        let watched = io.watched.clone();
+        let mut app = asset_app(io);
+        app.add_asset::<Leaf>()
+            .add_asset::<Refs>()
+            .init_asset_loader::<LeafLoader>()
+            .init_asset_loader::<RefsLoader>();
+        let (handle, state) = load::<Refs>(&mut app, "a/c.refs");
+        assert_eq!(state, LoadState::Loaded);
+
+        // handles from a template are relative to the template
+        let asset_server = app.world.resource::<AssetServer>();
+        let refs = app.world.resource::<Assets<Refs>>().get(&handle).unwrap();
+        let leaf = refs.refs[0].handle().unwrap();
+        assert_eq!(
+            asset_server.get_handle_path(leaf).unwrap().path(),
+            Path::new("d.leaf")
+        );
+
+        // changing either template reloads the file
+        let watched = watched.lock().unwrap();
+        for template in ["b.refs", "c.refs"] {
+            assert!(watched.contains(&(template.into(), Some("a/c.refs".into()))));
+            assert_ne!(asset_server.get_load_state(template), LoadState::NotLoaded);
+        }
+    }
+
+    #[test]
+    fn paths_above_the_root_fail_to_load() {
+        let mut app = app(&[
+            ("a/c.refs", r#"Refs(refs: [Serialized("../../d.leaf")])"#),
+            ("d.leaf", "Leaf"),
+        ]);
+        let (_, state) = load::<Refs>(&mut app, "a/c.refs");
+        assert_eq!(state, LoadState::Failed);
+    }
+}
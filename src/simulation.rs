@@ -0,0 +1,330 @@
+// fixed timestep simulation.
+//
+// gameplay runs in `CoreSchedule::FixedUpdate`, `TICK_RATE` times per second no matter the frame
+// rate, so the same seed and inputs always play out exactly the same. the schedule runs its
+// systems one at a time in a fixed order, all randomness comes from `SimRng`, and gameplay only
+// ever reads input through `PlayerInput`. bevy picks a different order for systems that aren't
+// ordered against each other every launch, so every gameplay system goes in one of the chained
+// `SimulationSet`s and is ordered against the others in its set that touch the same data. the
+// seed is logged at startup, and setting `BTLL_SEED` plays with that seed again.
+//
+// entities with `Interpolated` are drawn between their last two ticks: every tick starts by
+// putting their transform back where the simulation left it, and every frame moves it to where
+// it is between ticks.
+
+use bevy::{ecs::schedule::ExecutorKind, prelude::*};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::billboard_sprite::rotate_billboard_sprites;
+
+/// Gameplay ticks per second
+pub const TICK_RATE: f32 = 60.0;
+/// environment variable with the seed to play with, a random one is used without it
+pub const SEED_VAR: &str = "BTLL_SEED";
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FixedTime::new_from_secs(1.0 / TICK_RATE))
+            .init_resource::<SimRng>()
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                // the multithreaded executor can run unordered systems in any order
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+                schedule.configure_sets(
+                    (
+                        SimulationSet::Restore,
+                        SimulationSet::Player,
+                        SimulationSet::Enemies,
+                        SimulationSet::Bullets,
+                        SimulationSet::Damage,
+                        SimulationSet::Deaths,
+                        SimulationSet::Record,
+                    )
+                        .chain(),
+                );
+            })
+            .add_system(
+                restore_interpolated
+                    .in_set(SimulationSet::Restore)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                record_interpolated
+                    .in_set(SimulationSet::Record)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_startup_system(log_seed)
+            // billboards face the camera of the interpolated player, and override the rotation
+            .add_system(interpolate_transforms.before(rotate_billboard_sprites))
+            .register_type::<Interpolated>();
+    }
+}
+
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SimulationSet {
+    /// puts interpolated entities back where the last tick left them
+    Restore,
+    /// the player moves, shoots and uses and picks up items
+    Player,
+    /// enemies change phases, move and shoot
+    Enemies,
+    /// bullets move and hit whatever they reach
+    Bullets,
+    /// status effects and health tick, and damage is dealt
+    Damage,
+    /// the dead leave corpses and loot behind, and the player respawns
+    Deaths,
+    /// remembers where interpolated entities ended up
+    Record,
+}
+
+/// The only source of randomness for gameplay
+#[derive(Resource)]
+pub struct SimRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The seed this was started with, to play the same game again
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
+
+impl Default for SimRng {
+    fn default() -> Self {
+        let seed = std::env::var(SEED_VAR)
+            .ok()
+            .and_then(|seed| match seed.parse() {
+                Ok(seed) => Some(seed),
+                Err(err) => {
+                    warn!("{SEED_VAR} \"{seed}\" isn't a seed, using a random one: {err}");
+                    None
+                }
+            });
+        Self::new(seed.unwrap_or_else(|| rand::thread_rng().gen()))
+    }
+}
+
+fn log_seed(rng: Res<SimRng>) {
+    info!(
+        "playing with seed {}, set {SEED_VAR} to play it again",
+        rng.seed()
+    );
+}
+
+/// Transform of a moving entity at the last two ticks
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+pub struct Interpolated {
+    previous_translation: Vec3,
+    previous_rotation: Quat,
+    translation: Vec3,
+    rotation: Quat,
+}
+
+impl Interpolated {
+    pub fn new(transform: &Transform) -> Self {
+        Self {
+            previous_translation: transform.translation,
+            previous_rotation: transform.rotation,
+            translation: transform.translation,
+            rotation: transform.rotation,
+        }
+    }
+
+    /// Jump to `transform` without drawing the movement there, for teleports
+    pub fn snap(&mut self, transform: &Transform) {
+        *self = Self::new(transform);
+    }
+}
+
+pub fn restore_interpolated(mut query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in &mut query {
+        transform.translation = interpolated.translation;
+        transform.rotation = interpolated.rotation;
+        interpolated.previous_translation = interpolated.translation;
+        interpolated.previous_rotation = interpolated.rotation;
+    }
+}
+
+pub fn record_interpolated(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in &mut query {
+        interpolated.translation = transform.translation;
+        interpolated.rotation = transform.rotation;
+    }
+}
+
+pub fn interpolate_transforms(
+    mut query: Query<(&mut Transform, &Interpolated)>,
+    fixed_time: Res<FixedTime>,
+) {
+    // how far into the next tick this frame is
+    let alpha = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.0);
+    for (mut transform, interpolated) in &mut query {
+        transform.translation = interpolated
+            .previous_translation
+            .lerp(interpolated.translation, alpha);
+        transform.rotation = interpolated
+            .previous_rotation
+            .slerp(interpolated.rotation, alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bevy::ecs::schedule::{LogLevel, ScheduleBuildSettings};
+
+    use super::*;
+    use crate::{
+        bullet::{Bullet, BulletOptions, BulletPlugin, Team},
+        damage::DamagePlugin,
+        enemy::{behaviors::Behavior, Ai, AiPlugin, EnemyPlugin, Phase},
+        health::{Health, HealthPlugin},
+        items::ItemsPlugin,
+        player::{player_movement, Player, PlayerInput, PlayerPlugin, Velocity},
+        player_death::PlayerDeathPlugin,
+        shandle::SHandle,
+        status_effect::{StatusEffectPlugin, StatusEffects},
+        testing::{asset_app, MemoryAssetIo},
+    };
+
+    /// Enemies that chase the player and shoot at it inaccurately, so the rng decides where
+    /// bullets go. Returns where everything is after `ticks` ticks
+    fn play(seed: u64, ticks: u32) -> Vec<Vec3> {
+        let mut app = asset_app(MemoryAssetIo::default());
+        app.insert_resource(SimRng::new(seed))
+            .add_plugin(SimulationPlugin)
+            .add_plugin(BulletPlugin)
+            .add_plugin(DamagePlugin)
+            .add_plugin(HealthPlugin)
+            .add_plugin(StatusEffectPlugin)
+            .add_plugin(AiPlugin)
+            .add_system(
+                player_movement
+                    .in_set(SimulationSet::Player)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+
+        let bullet = app
+            .world
+            .resource_mut::<Assets<BulletOptions>>()
+            .add(BulletOptions {
+                sprite: SHandle::Loaded {
+                    path: "bullet.png".into(),
+                    handle: Handle::default(),
+                },
+                ..default()
+            });
+        let ai = Ai {
+            phases: HashMap::from([(
+                "Start".into(),
+                Phase {
+                    behaviors: vec![
+                        Behavior::ChasePlayer { speed: 1.0 },
+                        Behavior::ShootAtPlayer {
+                            bullet: SHandle::Loaded {
+                                path: "bullet.bullet".into(),
+                                handle: bullet,
+                            },
+                            interval: 0.1,
+                            predictive: true,
+                            inaccuracy: 30.0,
+                            timer: default(),
+                            volley: 0,
+                        },
+                    ],
+                    transitions: Vec::new(),
+                },
+            )]),
+            current: "Start".into(),
+        };
+        for i in 0..4 {
+            let transform = Transform::from_xyz(i as f32 * 3.0, 6.0, 0.0);
+            app.world.spawn((
+                SpatialBundle::from_transform(transform),
+                Health::new(100, Team::Enemy),
+                StatusEffects::default(),
+                Interpolated::new(&transform),
+                ai.clone(),
+            ));
+        }
+        let player = app
+            .world
+            .spawn((
+                SpatialBundle::default(),
+                Player,
+                Velocity::default(),
+                PlayerInput::default(),
+                StatusEffects::default(),
+                Interpolated::default(),
+                Health::new(1000, Team::Player),
+            ))
+            .id();
+
+        for tick in 0..ticks {
+            // the same inputs every game
+            let mut input = app.world.get_mut::<PlayerInput>(player).unwrap();
+            input.movement = Vec2::from_angle(tick as f32 * 0.05);
+            input.rotation = if tick % 60 < 30 { 1.0 } else { 0.0 };
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+
+        let mut query = app
+            .world
+            .query_filtered::<&Transform, Or<(With<Bullet>, With<Health>)>>();
+        query
+            .iter(&app.world)
+            .map(|transform| transform.translation)
+            .collect()
+    }
+
+    #[test]
+    fn the_same_seed_and_inputs_play_the_same() {
+        let game = play(1234, 180);
+        assert!(game.len() > 5, "no bullets were fired");
+        assert_eq!(game, play(1234, 180));
+        assert_ne!(game, play(4321, 180));
+    }
+
+    #[test]
+    fn gameplay_systems_run_in_a_fixed_order() {
+        let mut app = asset_app(MemoryAssetIo::default());
+        app.add_plugin(SimulationPlugin)
+            .add_plugin(PlayerPlugin)
+            .add_plugin(PlayerDeathPlugin)
+            .add_plugin(HealthPlugin)
+            .add_plugin(DamagePlugin)
+            .add_plugin(StatusEffectPlugin)
+            .add_plugin(EnemyPlugin)
+            .add_plugin(BulletPlugin)
+            .add_plugin(ItemsPlugin)
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule.set_build_settings(ScheduleBuildSettings {
+                    ambiguity_detection: LogLevel::Error,
+                    ..default()
+                });
+            });
+        app.world
+            .resource_scope(|world, mut schedules: Mut<Schedules>| {
+                let schedule = schedules.get_mut(&CoreSchedule::FixedUpdate).unwrap();
+                // `LogLevel::Warn` lists the systems instead
+                schedule.initialize(world)
+            })
+            .expect("systems that touch the same data aren't ordered");
+    }
+}
@@ -0,0 +1,366 @@
+// timed status effects.
+//
+// anything with `StatusEffects` can have effects applied to it, by bullets hitting it (through
+// the damage pipeline) or by items. movement, shooting and damage code asks the component how
+// it's affected, `tick_status_effects` counts the durations down and deals damage over time.
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    damage::{apply_damage, DamageEvent, DamageKind},
+    health::{tick_health, Health},
+    shandle::SHandleLoad,
+    simulation::SimulationSet,
+    validate::Problem,
+};
+
+pub const SLOWED_SPEED: f32 = 0.5;
+pub const SPEEDY_SPEED: f32 = 1.5;
+/// damage dealt while weak
+pub const WEAK_DAMAGE: f32 = 0.5;
+/// rate of fire while berserk
+pub const BERSERK_RATE_OF_FIRE: f32 = 1.25;
+/// damage per second
+pub const BLEEDING_DAMAGE: u32 = 10;
+/// damage per second
+pub const POISONED_DAMAGE: u32 = 6;
+/// health per second
+pub const HEALING_AMOUNT: u32 = 10;
+/// how often damage and healing over time happen, in seconds
+pub const PULSE: f32 = 0.5;
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            tick_status_effects
+                .after(tick_health)
+                .before(apply_damage)
+                .in_set(SimulationSet::Damage)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .register_type::<Effect>()
+        .register_type::<StatusEffects>();
+    }
+}
+
+#[derive(
+    Reflect,
+    FromReflect,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    SHandleLoad,
+)]
+pub enum Effect {
+    /// moves slower
+    Slowed,
+    /// can't move
+    Paralyzed,
+    /// can't shoot
+    Stunned,
+    /// movement is inverted
+    Confused,
+    /// deals less damage
+    Weak,
+    /// has no defense
+    ArmorBroken,
+    Bleeding,
+    Poisoned,
+    /// can't be damaged
+    Invulnerable,
+    /// moves faster
+    Speedy,
+    /// heals over time
+    Healing,
+    /// shoots faster
+    Berserk,
+}
+
+/// Effects currently on an entity, with how many seconds they have left
+#[derive(Component, Reflect, Debug, Clone)]
+pub struct StatusEffects {
+    effects: HashMap<Effect, f32>,
+    pulse: Timer,
+    /// whoever last applied effects, credited for the damage over time
+    pub applier: Option<Entity>,
+}
+
+impl Default for StatusEffects {
+    fn default() -> Self {
+        Self {
+            effects: HashMap::default(),
+            pulse: Timer::from_seconds(PULSE, TimerMode::Repeating),
+            applier: None,
+        }
+    }
+}
+
+impl StatusEffects {
+    /// Apply `effect` for `duration` seconds. The same effect doesn't stack, reapplying it only
+    /// makes it last longer if the new duration is longer than what's left.
+    pub fn apply(&mut self, effect: Effect, duration: f32) {
+        if duration <= 0.0 {
+            return;
+        }
+        let remaining = self.effects.entry(effect).or_insert(0.0);
+        *remaining = remaining.max(duration);
+    }
+
+    /// Apply all `effects`, `applier` being whoever applied them if anyone
+    pub fn apply_all(&mut self, effects: &[(Effect, f32)], applier: Option<Entity>) {
+        for (effect, duration) in effects {
+            self.apply(*effect, *duration);
+        }
+        if !effects.is_empty() && applier.is_some() {
+            self.applier = applier;
+        }
+    }
+
+    pub fn remove(&mut self, effect: Effect) {
+        self.effects.remove(&effect);
+    }
+
+    pub fn has(&self, effect: Effect) -> bool {
+        self.effects.contains_key(&effect)
+    }
+
+    /// Seconds left on `effect`, `None` if it isn't applied
+    pub fn remaining(&self, effect: Effect) -> Option<f32> {
+        self.effects.get(&effect).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Effect, f32)> + '_ {
+        self.effects
+            .iter()
+            .map(|(effect, remaining)| (*effect, *remaining))
+    }
+
+    /// Count all effects down by `delta` seconds and remove the ones that ran out.
+    /// Returns how many damage/healing over time pulses happened.
+    pub fn tick(&mut self, delta: f32) -> u32 {
+        self.pulse.tick(std::time::Duration::from_secs_f32(delta));
+        let pulses = self.pulse.times_finished_this_tick();
+        self.effects.retain(|_, remaining| {
+            *remaining -= delta;
+            *remaining > 0.0
+        });
+        if self.effects.is_empty() {
+            self.applier = None;
+        }
+        pulses
+    }
+
+    /// Multiplier for movement speed
+    pub fn speed(&self) -> f32 {
+        if self.has(Effect::Paralyzed) {
+            return 0.0;
+        }
+        let mut speed = 1.0;
+        if self.has(Effect::Slowed) {
+            speed *= SLOWED_SPEED;
+        }
+        if self.has(Effect::Speedy) {
+            speed *= SPEEDY_SPEED;
+        }
+        speed
+    }
+
+    /// Turn a movement direction the way the entity actually moves
+    pub fn movement(&self, movement: Vec2) -> Vec2 {
+        let movement = if self.has(Effect::Confused) {
+            -movement
+        } else {
+            movement
+        };
+        movement * self.speed()
+    }
+
+    pub fn can_shoot(&self) -> bool {
+        !self.has(Effect::Stunned)
+    }
+
+    /// Multiplier for rate of fire
+    pub fn rate_of_fire(&self) -> f32 {
+        if self.has(Effect::Berserk) {
+            BERSERK_RATE_OF_FIRE
+        } else {
+            1.0
+        }
+    }
+
+    /// Multiplier for damage dealt
+    pub fn damage_dealt(&self) -> f32 {
+        if self.has(Effect::Weak) {
+            WEAK_DAMAGE
+        } else {
+            1.0
+        }
+    }
+
+    /// Defense left after effects
+    pub fn defense(&self, defense: u32) -> u32 {
+        if self.has(Effect::ArmorBroken) {
+            0
+        } else {
+            defense
+        }
+    }
+
+    pub fn invulnerable(&self) -> bool {
+        self.has(Effect::Invulnerable)
+    }
+
+    /// Damage dealt by bleeding and poison over one pulse
+    pub fn damage_per_pulse(&self) -> u32 {
+        let mut damage = 0.0;
+        if self.has(Effect::Bleeding) {
+            damage += BLEEDING_DAMAGE as f32 * PULSE;
+        }
+        if self.has(Effect::Poisoned) {
+            damage += POISONED_DAMAGE as f32 * PULSE;
+        }
+        damage.round() as u32
+    }
+
+    /// Health healed over one pulse
+    pub fn healing_per_pulse(&self) -> u32 {
+        if self.has(Effect::Healing) {
+            (HEALING_AMOUNT as f32 * PULSE).round() as u32
+        } else {
+            0
+        }
+    }
+}
+
+pub fn validate_effects(effects: &[(Effect, f32)], problems: &mut Vec<Problem>) {
+    for (i, (effect, duration)) in effects.iter().enumerate() {
+        if *duration <= 0.0 {
+            problems.push(
+                Problem::new(
+                    format!("{effect:?} duration {duration} must be more than 0"),
+                    "",
+                )
+                .at(i.to_string()),
+            );
+        }
+    }
+}
+
+pub fn tick_status_effects(
+    mut query: Query<(Entity, &mut StatusEffects, Option<&mut Health>)>,
+    mut ev_damage: EventWriter<DamageEvent>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (entity, mut effects, health) in &mut query {
+        // damage and healing happen for effects that were on during the pulse
+        let damage = effects.damage_per_pulse();
+        let healing = effects.healing_per_pulse();
+        let pulses = effects.tick(fixed_time.period.as_secs_f32());
+        if pulses == 0 {
+            continue;
+        }
+
+        if damage > 0 {
+            ev_damage.send(DamageEvent {
+                target: entity,
+                source: effects.applier,
+                amount: damage * pulses,
+                damage_kind: DamageKind::OverTime,
+                effects: Vec::new(),
+            });
+        }
+        if let Some(mut health) = health {
+            if healing > 0 && !health.is_dead() {
+                health.heal(healing * pulses);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bullet::Team, damage::DamagePlugin, health::HealthPlugin};
+
+    #[test]
+    fn reapplying_only_makes_effects_last_longer() {
+        let mut effects = StatusEffects::default();
+        effects.apply(Effect::Poisoned, 2.0);
+        effects.apply(Effect::Poisoned, 1.0);
+        assert_eq!(effects.remaining(Effect::Poisoned), Some(2.0));
+        effects.apply(Effect::Poisoned, 3.0);
+        assert_eq!(effects.remaining(Effect::Poisoned), Some(3.0));
+        // the same effect doesn't stack
+        assert_eq!(effects.iter().count(), 1);
+        assert_eq!(
+            effects.damage_per_pulse(),
+            (POISONED_DAMAGE as f32 * PULSE).round() as u32
+        );
+
+        effects.apply(Effect::Slowed, 0.0);
+        effects.apply(Effect::Slowed, -1.0);
+        assert!(!effects.has(Effect::Slowed));
+    }
+
+    #[test]
+    fn effects_expire_when_nothing_is_left() {
+        let mut effects = StatusEffects::default();
+        effects.apply(Effect::Weak, 1.0);
+        effects.tick(0.5);
+        assert_eq!(effects.remaining(Effect::Weak), Some(0.5));
+        effects.tick(0.5);
+        assert!(!effects.has(Effect::Weak));
+    }
+
+    #[test]
+    fn pulses_carry_over_between_ticks() {
+        let mut effects = StatusEffects::default();
+        assert_eq!(effects.tick(PULSE * 0.6), 0);
+        assert_eq!(effects.tick(PULSE * 0.6), 1);
+        assert_eq!(effects.tick(PULSE * 0.3), 0);
+        assert_eq!(effects.tick(PULSE * 2.0), 2);
+    }
+
+    #[test]
+    fn the_applier_is_forgotten_once_effects_run_out() {
+        let mut effects = StatusEffects::default();
+        let applier = Entity::from_raw(7);
+        effects.apply_all(&[(Effect::Bleeding, 1.0)], Some(applier));
+        // items applying effects don't take the credit away
+        effects.apply_all(&[(Effect::Speedy, 0.5)], None);
+        assert_eq!(effects.applier, Some(applier));
+        effects.tick(0.5);
+        assert_eq!(effects.applier, Some(applier));
+        effects.tick(0.5);
+        assert_eq!(effects.applier, None);
+    }
+
+    #[test]
+    fn poison_kills_are_credited_to_whoever_applied_it() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(FixedTime::new_from_secs(PULSE))
+            .add_plugin(DamagePlugin)
+            .add_plugin(HealthPlugin)
+            .add_plugin(StatusEffectPlugin);
+        let attacker = app.world.spawn_empty().id();
+        let mut effects = StatusEffects::default();
+        effects.apply_all(&[(Effect::Poisoned, 10.0)], Some(attacker));
+        let target = app.world.spawn((Health::new(5, Team::Enemy), effects)).id();
+
+        for _ in 0..2 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        let health = app.world.get::<Health>(target).unwrap();
+        assert!(health.is_dead());
+        assert_eq!(health.last_attacker, Some(attacker));
+    }
+}
@@ -0,0 +1,1083 @@
+// template inheritance for ron content files.
+//
+// a file can start with `extends: "base.enemy"` to use another file as its template,
+// its own fields are then deep-merged over the fields of the template:
+// - structs with the same name (or no name) are merged field by field
+// - maps are merged key by key
+// - anything else (lists, enum variants, numbers, ...) replaces the template's value
+//
+// ron extensions enabled by `#![enable(...)]` headers in any file of a chain are enabled for
+// the whole chain.
+
+use std::{fmt, ops::Range, path::Path};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    content_error::ContentError,
+    shandle::{deserialize_ron, resolve_path},
+};
+
+/// Resolves the chain of templates a content file extends.
+///
+/// The files themselves are read by the caller, which keeps this usable both from asset
+/// loaders and from tools reading straight from disk:
+/// ```ignore
+/// if let Some(mut chain) = TemplateChain::new(path, bytes)? {
+///     while let Some(parent) = chain.next_parent()? {
+///         let bytes = read(&parent)?;
+///         chain.push_parent(parent, &bytes)?;
+///     }
+///     let merged = chain.finish();
+/// }
+/// ```
+pub struct TemplateChain {
+    /// files in the chain, starting from the file being loaded
+    files: Vec<String>,
+    /// parsed files in the same order as `files`
+    layers: Vec<Node>,
+    /// text of the files in the same order as `files`, to point errors back at them
+    texts: Vec<String>,
+    /// what the last file in the chain extends
+    extends: Option<String>,
+    /// ron extensions enabled by any file in the chain
+    extensions: Vec<String>,
+}
+
+impl TemplateChain {
+    /// Returns `None` if the file doesn't extend anything and can be deserialized as is
+    pub fn new(path: &Path, bytes: &[u8]) -> Result<Option<Self>, ContentError> {
+        let Ok(text) = std::str::from_utf8(bytes) else {
+            return Ok(None);
+        };
+        let mut parser = Parser::new(text);
+        let mut node = match parser.parse() {
+            Ok(node) => node,
+            // ron knows nothing of templates, and would only complain about `extends`
+            Err(err) if text.contains("extends") => return Err(err),
+            // leave reporting syntax errors to ron
+            Err(_) => return Ok(None),
+        };
+
+        let Some(extends) = node.take_extends()? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            files: vec![path.to_string_lossy().replace('\\', "/")],
+            layers: vec![node],
+            texts: vec![text.to_string()],
+            extends: Some(extends),
+            extensions: parser.extensions,
+        }))
+    }
+
+    /// The asset path of the next template that needs to be read, if any
+    pub fn next_parent(&self) -> Result<Option<String>, ContentError> {
+        let Some(extends) = &self.extends else {
+            return Ok(None);
+        };
+        let current = self.files.last().unwrap();
+        let path = resolve_path(Path::new(current), extends)?;
+
+        if self.files.contains(&path) {
+            return Err(ContentError::new(format!(
+                "template cycle: {} -> {path}",
+                self.files.join(" -> ")
+            )));
+        }
+
+        Ok(Some(path))
+    }
+
+    /// Add the contents of the template returned by [`TemplateChain::next_parent`]
+    pub fn push_parent(&mut self, path: String, bytes: &[u8]) -> Result<(), ContentError> {
+        let mut files = self.files.clone();
+        files.push(path.clone());
+        let in_template = |err: ContentError| err.in_file(files.clone());
+
+        let text = std::str::from_utf8(bytes)
+            .map_err(|err| in_template(ContentError::new(err.to_string())))?;
+        let mut parser = Parser::new(text);
+        let mut node = parser.parse().map_err(in_template)?;
+
+        self.extends = node.take_extends().map_err(in_template)?;
+        // handles in the template are relative to the template, not to the file extending it
+        node.rebase_handles(Path::new(&path)).map_err(in_template)?;
+
+        self.files.push(path);
+        self.layers.push(node);
+        self.texts.push(text.to_string());
+        for extension in parser.extensions {
+            if !self.extensions.contains(&extension) {
+                self.extensions.push(extension);
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge all files of the chain into a single ron string
+    pub fn finish(&self) -> String {
+        let merged = merge_layers(self.layers.clone());
+        if self.extensions.is_empty() {
+            merged.to_string()
+        } else {
+            format!("#![enable({})]\n{merged}", self.extensions.join(", "))
+        }
+    }
+
+    /// Turn a serialized asset back into a file extending the same template as the first file
+    /// of the chain, keeping only the values that differ from the template.
+    ///
+    /// Used to write the asset back out without inlining its templates.
+    pub fn unmerge(&self, written: &str) -> Result<String, ContentError> {
+        let template = merge_layers(self.layers[1..].to_vec());
+        let extends = Node::Atom(ron::to_string(&self.first_extends()?)?);
+        let written = Parser::new(written).parse()?;
+        let name = match &written {
+            Node::Struct(name, _) => name.clone(),
+            _ => None,
+        };
+        let mut fields = match written.diff(&template) {
+            Some(Node::Struct(_, fields)) => fields,
+            Some(_) => return Err(ContentError::new("only structs can extend a template")),
+            None => Vec::new(),
+        };
+        fields.insert(0, ("extends".to_string(), extends));
+        Ok(Node::Struct(name, fields).to_string())
+    }
+
+    /// Whether `child` is the first file of the chain written back out without losing anything:
+    /// it extends the same template, and merged with it still has everything the first file had
+    pub fn preserved_by(&self, child: &str) -> Result<bool, ContentError> {
+        let mut node = Parser::new(child).parse()?;
+        if node.take_extends()? != Some(self.first_extends()?) {
+            return Ok(false);
+        }
+        let mut layers = self.layers.clone();
+        let original = merge_layers(layers.clone());
+        layers[0] = node;
+        Ok(original.covered_by(&merge_layers(layers)))
+    }
+
+    /// What the first file of the chain extends, as written
+    fn first_extends(&self) -> Result<String, ContentError> {
+        let extends = Parser::new(&self.texts[0]).parse()?.take_extends()?;
+        Ok(extends.unwrap_or_default())
+    }
+
+    /// Merge all files of the chain and deserialize the result
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, ContentError> {
+        deserialize_ron(self.finish().as_bytes()).map_err(|err| self.locate(err))
+    }
+
+    /// Point an error in the merged text at the file of the chain its field was written in.
+    ///
+    /// Positions in the merged text don't match any file, so the error is put at the start of
+    /// the closest value around its field, in the first file that has it.
+    fn locate(&self, err: ContentError) -> ContentError {
+        let mut located = ContentError {
+            position: None,
+            message: format!("{} (after merging with its templates)", err.message),
+            ..err
+        };
+        let Some(field) = &located.field else {
+            return located;
+        };
+        // `behaviors[1].interval` is `behaviors`, `1`, `interval` in value spans
+        let segments: Vec<&str> = field
+            .split(['.', '[', ']'])
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let mut best: Option<(usize, usize, Range<usize>)> = None;
+        for (i, text) in self.texts.iter().enumerate() {
+            let Ok(spans) = value_spans(text) else {
+                continue;
+            };
+            let find = |path: &[String]| {
+                spans
+                    .iter()
+                    .find(|(other, _)| other[..] == path[..])
+                    .map(|(_, span)| span.clone())
+            };
+            // segments that aren't in the spans, like enum variant names, are skipped
+            let mut path = Vec::new();
+            let mut span = None;
+            for segment in &segments {
+                path.push(segment.to_string());
+                match find(&path) {
+                    Some(found) => span = Some(found),
+                    None => {
+                        path.pop();
+                    }
+                }
+            }
+            if let Some(span) = span {
+                if best.as_ref().is_none_or(|(_, len, _)| path.len() > *len) {
+                    best = Some((i, path.len(), span));
+                }
+            }
+        }
+
+        if let Some((i, _, span)) = best {
+            located.chain = self.files[..=i].to_vec();
+            located.position = Some(line_col(&self.texts[i], span.start));
+        }
+        located
+    }
+}
+
+/// Checks that everything written in `original` is also in `written`, ignoring formatting,
+/// field order and fields only `written` has (like ones left to their default).
+///
+/// Used to make sure content survives being serialized again.
+pub fn preserves(original: &str, written: &str) -> Result<bool, ContentError> {
+    let original = Parser::new(original).parse()?;
+    let written = Parser::new(written).parse()?;
+    Ok(original.covered_by(&written))
+}
+
+/// Paths to values in a ron file with where the values are, see [`value_spans`]
+pub type ValueSpans = Vec<(Vec<String>, Range<usize>)>;
+
+/// Where each value of a ron file is, by the path of field names, map keys and list indices
+/// leading to it (the same paths as [`Problem::field`](crate::validate::Problem::field)).
+///
+/// Values are listed after the values nested in them, the whole file is the last one.
+pub fn value_spans(text: &str) -> Result<ValueSpans, ContentError> {
+    let mut parser = Parser::new(text);
+    parser.parse()?;
+    Ok(parser.spans)
+}
+
+/// Merge layers of a chain, each layer over the ones after it
+fn merge_layers(mut layers: Vec<Node>) -> Node {
+    let mut merged = layers.pop().unwrap();
+    while let Some(child) = layers.pop() {
+        merged = merged.merge(child);
+    }
+    merged
+}
+
+/// 1-based line and column of a byte offset into `text`
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let col = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+    (line, col)
+}
+
+/// Structure of a ron file, keeping struct and enum variant names (unlike `ron::Value`)
+#[derive(Clone, Debug)]
+enum Node {
+    /// numbers, strings, bools, unit variants, ... kept as they were written
+    Atom(String),
+    /// `Name(field: value)` or `(field: value)`
+    Struct(Option<String>, Vec<(String, Node)>),
+    /// `Name(value)` or `(value)`
+    Tuple(Option<String>, Vec<Node>),
+    /// `{ key: value }`
+    Map(Vec<(Node, Node)>),
+    /// `[value]`
+    List(Vec<Node>),
+}
+
+impl Node {
+    /// Removes the top level `extends` field, returning its path
+    fn take_extends(&mut self) -> Result<Option<String>, ContentError> {
+        let Node::Struct(_, fields) = self else {
+            return Ok(None);
+        };
+        let Some(index) = fields.iter().position(|(name, _)| name == "extends") else {
+            return Ok(None);
+        };
+        match fields.remove(index).1 {
+            Node::Atom(atom) => Ok(Some(ron::de::from_str::<String>(&atom)?)),
+            _ => Err(ContentError::new("extends must be a path")),
+        }
+    }
+
+    /// Turns the paths of serialized handles into paths from the asset root
+    fn rebase_handles(&mut self, base: &Path) -> Result<(), ContentError> {
+        match self {
+            Node::Tuple(Some(name), values) if name == "Serialized" && values.len() == 1 => {
+                if let Node::Atom(atom) = &values[0] {
+                    if let Ok(path) = ron::de::from_str::<String>(atom) {
+                        let path = format!("/{}", resolve_path(base, &path)?);
+                        values[0] = Node::Atom(ron::to_string(&path)?);
+                    }
+                }
+            }
+            Node::Atom(_) => {}
+            Node::Struct(_, fields) => {
+                for (_, value) in fields {
+                    value.rebase_handles(base)?;
+                }
+            }
+            Node::Tuple(_, values) | Node::List(values) => {
+                for value in values {
+                    value.rebase_handles(base)?;
+                }
+            }
+            Node::Map(entries) => {
+                for (_, value) in entries {
+                    value.rebase_handles(base)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether every value in `self` is also in `other`, see [`preserves`]
+    fn covered_by(&self, other: &Node) -> bool {
+        fn same_name(a: &Option<String>, b: &Option<String>) -> bool {
+            a.is_none() || b.is_none() || a == b
+        }
+        fn all_covered(a: &[Node], b: &[Node]) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.covered_by(b))
+        }
+
+        match (self, other) {
+            (Node::Atom(a), Node::Atom(b)) => {
+                a == b
+                    || matches!((a.parse::<f64>(), b.parse::<f64>()), (Ok(a), Ok(b)) if a == b)
+                    || matches!(
+                        (ron::from_str::<String>(a), ron::from_str::<String>(b)),
+                        (Ok(a), Ok(b)) if a == b
+                    )
+            }
+            (Node::Struct(name, fields), Node::Struct(other_name, other_fields)) => {
+                same_name(name, other_name)
+                    && fields.iter().all(|(key, value)| {
+                        other_fields
+                            .iter()
+                            .any(|(other_key, other)| key == other_key && value.covered_by(other))
+                    })
+            }
+            (Node::Tuple(name, values), Node::Tuple(other_name, other_values)) => {
+                same_name(name, other_name) && all_covered(values, other_values)
+            }
+            (Node::List(values), Node::List(other_values)) => all_covered(values, other_values),
+            (Node::Map(entries), Node::Map(other_entries)) => {
+                entries.len() == other_entries.len()
+                    && entries.iter().all(|(key, value)| {
+                        other_entries.iter().any(|(other_key, other)| {
+                            key.covered_by(other_key) && value.covered_by(other)
+                        })
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    /// What would need to be merged over `template` to get `self`, `None` if it's the same.
+    ///
+    /// The opposite of [`Node::merge`], except values can't be removed from `template`.
+    fn diff(self, template: &Node) -> Option<Node> {
+        match (self, template) {
+            (Node::Struct(name, fields), Node::Struct(template_name, template_fields))
+                if name.is_none() || template_name.is_none() || &name == template_name =>
+            {
+                let fields: Vec<_> = fields
+                    .into_iter()
+                    .filter_map(|(key, value)| {
+                        match template_fields.iter().find(|(k, _)| k == &key) {
+                            Some((_, template)) => value.diff(template).map(|value| (key, value)),
+                            None => Some((key, value)),
+                        }
+                    })
+                    .collect();
+                (!fields.is_empty()).then_some(Node::Struct(name, fields))
+            }
+            (Node::Map(entries), Node::Map(template_entries)) => {
+                let entries: Vec<_> = entries
+                    .into_iter()
+                    .filter_map(|(key, value)| {
+                        let printed = key.to_string();
+                        match template_entries
+                            .iter()
+                            .find(|(k, _)| k.to_string() == printed)
+                        {
+                            Some((_, template)) => value.diff(template).map(|value| (key, value)),
+                            None => Some((key, value)),
+                        }
+                    })
+                    .collect();
+                (!entries.is_empty()).then_some(Node::Map(entries))
+            }
+            (value, template) => {
+                (!(value.covered_by(template) && template.covered_by(&value))).then_some(value)
+            }
+        }
+    }
+
+    /// Merge `child` over `self`
+    fn merge(self, child: Node) -> Node {
+        match (self, child) {
+            (Node::Struct(name, mut fields), Node::Struct(child_name, child_fields))
+                if child_name.is_none() || name.is_none() || child_name == name =>
+            {
+                for (key, value) in child_fields {
+                    match fields.iter().position(|(k, _)| k == &key) {
+                        Some(i) => {
+                            let parent = std::mem::replace(&mut fields[i].1, Node::List(vec![]));
+                            fields[i].1 = parent.merge(value);
+                        }
+                        None => fields.push((key, value)),
+                    }
+                }
+                Node::Struct(child_name.or(name), fields)
+            }
+            (Node::Map(mut entries), Node::Map(child_entries)) => {
+                for (key, value) in child_entries {
+                    let printed = key.to_string();
+                    match entries.iter().position(|(k, _)| k.to_string() == printed) {
+                        Some(i) => {
+                            let parent = std::mem::replace(&mut entries[i].1, Node::List(vec![]));
+                            entries[i].1 = parent.merge(value);
+                        }
+                        None => entries.push((key, value)),
+                    }
+                }
+                Node::Map(entries)
+            }
+            (_, child) => child,
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T>(
+            f: &mut fmt::Formatter<'_>,
+            items: &[T],
+            mut item: impl FnMut(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+        ) -> fmt::Result {
+            for (i, value) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                item(f, value)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Node::Atom(atom) => write!(f, "{atom}"),
+            Node::Struct(name, fields) => {
+                write!(f, "{}(", name.as_deref().unwrap_or(""))?;
+                list(f, fields, |f, (key, value)| write!(f, "{key}: {value}"))?;
+                write!(f, ")")
+            }
+            Node::Tuple(name, values) => {
+                write!(f, "{}(", name.as_deref().unwrap_or(""))?;
+                list(f, values, |f, value| write!(f, "{value}"))?;
+                write!(f, ")")
+            }
+            Node::Map(entries) => {
+                write!(f, "{{")?;
+                list(f, entries, |f, (key, value)| write!(f, "{key}: {value}"))?;
+                write!(f, "}}")
+            }
+            Node::List(values) => {
+                write!(f, "[")?;
+                list(f, values, |f, value| write!(f, "{value}"))?;
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// path to the value being parsed, for [`value_spans`]
+    path: Vec<String>,
+    spans: ValueSpans,
+    /// extensions enabled by `#![enable(...)]` headers
+    extensions: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            path: Vec::new(),
+            spans: Vec::new(),
+            extensions: Vec::new(),
+        }
+    }
+
+    fn parse(&mut self) -> Result<Node, ContentError> {
+        self.headers()?;
+        let node = self.value()?;
+        self.skip_whitespace();
+        if self.pos < self.src.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(node)
+    }
+
+    /// Parses the `#![enable(...)]` headers at the start of a file
+    fn headers(&mut self) -> Result<(), ContentError> {
+        loop {
+            self.skip_whitespace();
+            if !self.rest().starts_with("#!") {
+                return Ok(());
+            }
+            self.pos += 2;
+            self.expect('[')?;
+            self.skip_whitespace();
+            if self.word() != "enable" {
+                return Err(self.error("expected `enable`"));
+            }
+            self.expect('(')?;
+            let extensions = self.separated(')', |p| {
+                p.skip_whitespace();
+                match p.word() {
+                    "" => Err(p.error("expected an extension")),
+                    extension => Ok(extension.to_string()),
+                }
+            })?;
+            self.expect(']')?;
+            for extension in extensions {
+                if !self.extensions.contains(&extension) {
+                    self.extensions.push(extension);
+                }
+            }
+        }
+    }
+
+    fn error(&self, message: &str) -> ContentError {
+        ContentError {
+            position: Some(line_col(self.src, self.pos)),
+            ..ContentError::new(message)
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if trimmed.starts_with("/*") {
+                self.pos += trimmed.find("*/").map_or(trimmed.len(), |end| end + 2);
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ContentError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{c}`")))
+        }
+    }
+
+    fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '+' | '-')))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn value(&mut self) -> Result<Node, ContentError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let node = self.node()?;
+        self.spans.push((self.path.clone(), start..self.pos));
+        Ok(node)
+    }
+
+    /// Parses a value nested in the current one under `key`
+    fn child(&mut self, key: String) -> Result<Node, ContentError> {
+        self.path.push(key);
+        let node = self.value();
+        self.path.pop();
+        node
+    }
+
+    fn node(&mut self) -> Result<Node, ContentError> {
+        let start = self.pos;
+        match self.peek() {
+            Some('"') | Some('\'') => {
+                self.string()?;
+                Ok(Node::Atom(self.src[start..self.pos].to_string()))
+            }
+            Some('[') => {
+                self.pos += 1;
+                Ok(Node::List(self.indexed(']')?))
+            }
+            Some('{') => {
+                self.pos += 1;
+                Ok(Node::Map(self.separated('}', |p| {
+                    let key = p.value()?;
+                    p.expect(':')?;
+                    // string keys are named without their quotes
+                    let name = match &key {
+                        Node::Atom(atom) => ron::from_str(atom).unwrap_or_else(|_| atom.clone()),
+                        key => key.to_string(),
+                    };
+                    Ok((key, p.child(name)?))
+                })?))
+            }
+            Some('(') => self.parens(None),
+            Some(_) => {
+                let word = self.word();
+                if word.is_empty() {
+                    return Err(self.error("unexpected character"));
+                }
+                if word == "r" && matches!(self.peek(), Some('"') | Some('#')) {
+                    self.raw_string()?;
+                    return Ok(Node::Atom(self.src[start..self.pos].to_string()));
+                }
+
+                let is_ident = word.starts_with(|c: char| c.is_alphabetic() || c == '_');
+                self.skip_whitespace();
+                if is_ident && self.peek() == Some('(') {
+                    self.parens(Some(word.to_string()))
+                } else {
+                    Ok(Node::Atom(word.to_string()))
+                }
+            }
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    /// Parses the contents of `(...)`, which is either a struct or a tuple
+    fn parens(&mut self, name: Option<String>) -> Result<Node, ContentError> {
+        self.expect('(')?;
+
+        // a struct if the first thing inside is `ident:`
+        self.skip_whitespace();
+        let start = self.pos;
+        let is_struct = !self.word().is_empty() && self.eat(':');
+        self.pos = start;
+
+        if is_struct {
+            let fields = self.separated(')', |p| {
+                p.skip_whitespace();
+                let key = p.word().to_string();
+                p.expect(':')?;
+                let value = p.child(key.clone())?;
+                Ok((key, value))
+            })?;
+            Ok(Node::Struct(name, fields))
+        } else {
+            Ok(Node::Tuple(name, self.indexed(')')?))
+        }
+    }
+
+    /// Parses comma separated values up to and including `end`, named by their index
+    fn indexed(&mut self, end: char) -> Result<Vec<Node>, ContentError> {
+        let mut index = 0;
+        self.separated(end, |p| {
+            let value = p.child(index.to_string());
+            index += 1;
+            value
+        })
+    }
+
+    /// Parses comma separated items up to and including `end`
+    fn separated<T>(
+        &mut self,
+        end: char,
+        mut item: impl FnMut(&mut Self) -> Result<T, ContentError>,
+    ) -> Result<Vec<T>, ContentError> {
+        let mut items = Vec::new();
+        loop {
+            if self.eat(end) {
+                return Ok(items);
+            }
+            items.push(item(self)?);
+            if !self.eat(',') {
+                self.expect(end)?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<(), ContentError> {
+        let quote = self.peek().unwrap();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            if c == '\\' {
+                chars.next();
+            } else if c == quote {
+                self.pos += i + 1;
+                return Ok(());
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn raw_string(&mut self) -> Result<(), ContentError> {
+        let rest = self.rest();
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let body = &rest[hashes..];
+        if !body.starts_with('"') {
+            return Err(self.error("expected `\"`"));
+        }
+        match body[1..].find(&terminator) {
+            Some(end) => {
+                self.pos += hashes + 1 + end + terminator.len();
+                Ok(())
+            }
+            None => Err(self.error("unterminated raw string")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Node {
+        Parser::new(text).parse().unwrap()
+    }
+
+    fn merge(parent: &str, child: &str) -> String {
+        parse(parent).merge(parse(child)).to_string()
+    }
+
+    /// Resolve and merge a chain of in-memory files, starting from the first one
+    fn chain(files: &[(&str, &str)]) -> Result<String, ContentError> {
+        let (path, text) = files[0];
+        let mut chain = TemplateChain::new(Path::new(path), text.as_bytes())?.unwrap();
+        while let Some(parent) = chain.next_parent()? {
+            let (_, text) = files.iter().find(|(path, _)| *path == parent).unwrap();
+            chain.push_parent(parent, text.as_bytes())?;
+        }
+        Ok(chain.finish())
+    }
+
+    #[test]
+    fn parses_ron() {
+        let text = r##"
+            // comment
+            Options (
+                name: "a, b: (c)",
+                raw: r#"say "hi""#,
+                list: [1, -2.5e3, true,],
+                map: { "a": Some(1), 2: None },
+                /* comment */ tuple: (1, Unit),
+                named: Pair(a: 1, b: 2),
+            )
+        "##;
+        assert_eq!(
+            parse(text).to_string(),
+            r##"Options(name: "a, b: (c)", raw: r#"say "hi""#, list: [1, -2.5e3, true], map: {"a": Some(1), 2: None}, tuple: (1, Unit), named: Pair(a: 1, b: 2))"##
+        );
+    }
+
+    #[test]
+    fn parse_errors_have_a_position() {
+        let err = Parser::new("Options (\n    a: [1, 2\n)")
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.position, Some((3, 1)));
+        let err = Parser::new("(a: \"unterminated)").parse().unwrap_err();
+        assert_eq!(err.position, Some((1, 5)));
+        assert!(Parser::new("(a: 1) b").parse().is_err());
+    }
+
+    #[test]
+    fn merges_structs_and_maps_deeply() {
+        assert_eq!(
+            merge(
+                "E(health: H(max: 10, current: 10), ai: A(current: \"a\"))",
+                "E(health: (max: 20), sprite: \"b.png\")",
+            ),
+            "E(health: H(max: 20, current: 10), ai: A(current: \"a\"), sprite: \"b.png\")"
+        );
+        assert_eq!(
+            merge(
+                "{\"a\": P(x: 1, y: 2), \"b\": P(x: 3)}",
+                "{\"a\": P(y: 5), \"c\": P()}"
+            ),
+            "{\"a\": P(x: 1, y: 5), \"b\": P(x: 3), \"c\": P()}"
+        );
+    }
+
+    #[test]
+    fn merge_replaces_everything_else() {
+        // lists
+        assert_eq!(merge("(a: [1, 2, 3])", "(a: [4])"), "(a: [4])");
+        // enum variants with different names
+        assert_eq!(
+            merge("(ai: Serialized(\"a.ai\"))", "(ai: Ai(current: \"a\"))"),
+            "(ai: Ai(current: \"a\"))"
+        );
+        assert_eq!(
+            merge("(a: Wave(x: 1))", "(a: Spin(y: 2))"),
+            "(a: Spin(y: 2))"
+        );
+        assert_eq!(merge("(a: 1)", "(a: 2)"), "(a: 2)");
+    }
+
+    #[test]
+    fn resolves_chains() {
+        let merged = chain(&[
+            ("c.enemy", "E(extends: \"b.enemy\", c: 3)"),
+            ("b.enemy", "E(extends: \"./a.enemy\", b: 2, c: 2)"),
+            ("a.enemy", "E(a: 1, b: 1, c: 1)"),
+        ])
+        .unwrap();
+        assert_eq!(merged, "E(a: 1, b: 2, c: 3)");
+    }
+
+    #[test]
+    fn files_without_extends_are_not_chains() {
+        assert!(TemplateChain::new(Path::new("a.enemy"), b"E(a: 1)")
+            .unwrap()
+            .is_none());
+        // syntax errors are left to ron
+        assert!(TemplateChain::new(Path::new("a.enemy"), b"E(a: ")
+            .unwrap()
+            .is_none());
+        // unless the file extends something, ron would only see a field it doesn't know
+        assert!(TemplateChain::new(Path::new("a.enemy"), b"E(extends: \"b.enemy\", a: ").is_err());
+    }
+
+    #[test]
+    fn extension_headers_are_kept() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Options {
+            a: Option<u32>,
+            b: Option<u32>,
+        }
+
+        let files = [
+            (
+                "a.enemy",
+                "#![enable(implicit_some)]\n// comment\nOptions(extends: \"b.enemy\", a: 1)",
+            ),
+            (
+                "b.enemy",
+                "#![enable(unwrap_newtypes, implicit_some)] Options(b: 2)",
+            ),
+        ];
+        assert_eq!(
+            chain(&files).unwrap(),
+            "#![enable(implicit_some, unwrap_newtypes)]\nOptions(b: 2, a: 1)"
+        );
+
+        let (path, text) = files[0];
+        let mut chain = TemplateChain::new(Path::new(path), text.as_bytes())
+            .unwrap()
+            .unwrap();
+        chain
+            .push_parent("b.enemy".into(), files[1].1.as_bytes())
+            .unwrap();
+        assert_eq!(
+            chain.deserialize::<Options>().unwrap(),
+            Options {
+                a: Some(1),
+                b: Some(2)
+            }
+        );
+
+        assert!(Parser::new("#![enable()] E()").parse().is_ok());
+        assert!(Parser::new("#![disable(implicit_some)] E()")
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let err = chain(&[
+            ("a.enemy", "E(extends: \"b.enemy\")"),
+            ("b.enemy", "E(extends: \"./a.enemy\")"),
+        ])
+        .unwrap_err();
+        assert_eq!(err.message, "template cycle: a.enemy -> b.enemy -> a.enemy");
+
+        let err = chain(&[("a.enemy", "E(extends: \"a.enemy\")")]).unwrap_err();
+        assert_eq!(err.message, "template cycle: a.enemy -> a.enemy");
+    }
+
+    #[test]
+    fn errors_in_templates_name_the_chain() {
+        let err = chain(&[
+            ("a.enemy", "E(extends: \"b.enemy\")"),
+            ("b.enemy", "E(b: [)"),
+        ])
+        .unwrap_err();
+        assert_eq!(err.chain, ["a.enemy", "b.enemy"]);
+        assert!(err.position.is_some());
+    }
+
+    #[test]
+    fn errors_after_merging_point_at_the_file_with_the_field() {
+        #[derive(serde::Deserialize, Debug)]
+        struct Options {
+            #[allow(dead_code)]
+            health: Health,
+        }
+        #[derive(serde::Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Health {
+            max: u32,
+            regen: f32,
+        }
+
+        let deserialize = |files: &[(&str, &str)]| {
+            let (path, text) = files[0];
+            let mut chain = TemplateChain::new(Path::new(path), text.as_bytes())
+                .unwrap()
+                .unwrap();
+            while let Some(parent) = chain.next_parent().unwrap() {
+                let (_, text) = files.iter().find(|(path, _)| *path == parent).unwrap();
+                chain.push_parent(parent, text.as_bytes()).unwrap();
+            }
+            chain.deserialize::<Options>().unwrap_err()
+        };
+
+        // in the template
+        let err = deserialize(&[
+            ("a.enemy", "Options(extends: \"b.enemy\", health: (max: 1))"),
+            (
+                "b.enemy",
+                "Options(\n    health: Health(max: 2, regen: \"fast\"),\n)",
+            ),
+        ]);
+        assert_eq!(err.chain, ["a.enemy", "b.enemy"]);
+        assert_eq!(err.field.as_deref(), Some("health.regen"));
+        assert_eq!(err.position, Some((2, 35)));
+
+        // overridden in the file itself
+        let err = deserialize(&[
+            (
+                "a.enemy",
+                "Options(extends: \"b.enemy\", health: (max: -1))",
+            ),
+            ("b.enemy", "Options(health: Health(max: 2, regen: 1.0))"),
+        ]);
+        assert_eq!(err.chain, ["a.enemy"]);
+        assert_eq!(err.position, Some((1, 43)));
+    }
+
+    #[test]
+    fn rebases_handles_in_templates() {
+        let merged = chain(&[
+            (
+                "enemies/big/boss.enemy",
+                "E(extends: \"../base.enemy\", sprite: Serialized(\"./boss.png\"))",
+            ),
+            (
+                "enemies/base.enemy",
+                "E(
+                    sprite: Serialized(\"./base.png\"),
+                    ai: Serialized(\"../ais/base.ai\"),
+                    drops: [(Serialized(\"potion.item\"), 0.5)],
+                )",
+            ),
+        ])
+        .unwrap();
+        // the child's own handles are still relative to the child
+        assert_eq!(
+            merged,
+            "E(sprite: Serialized(\"./boss.png\"), ai: Serialized(\"/ais/base.ai\"), drops: [(Serialized(\"/potion.item\"), 0.5)])"
+        );
+
+        let err = chain(&[
+            ("a.enemy", "E(extends: \"b.enemy\")"),
+            ("b.enemy", "E(sprite: Serialized(\"../a.png\"))"),
+        ])
+        .unwrap_err();
+        assert_eq!(err.chain, ["a.enemy", "b.enemy"]);
+    }
+
+    #[test]
+    fn unmerges_back_into_a_file_extending_the_template() {
+        let files = [
+            (
+                "a/c.enemy",
+                "E(extends: \"../b.enemy\", h: H(max: 2), s: \"c\")",
+            ),
+            (
+                "b.enemy",
+                "E(h: H(max: 1, current: 1), s: Serialized(\"./b.png\"), m: {\"x\": 1, \"y\": 2})",
+            ),
+        ];
+        let mut chain = TemplateChain::new(Path::new(files[0].0), files[0].1.as_bytes())
+            .unwrap()
+            .unwrap();
+        let parent = chain.next_parent().unwrap().unwrap();
+        chain.push_parent(parent, files[1].1.as_bytes()).unwrap();
+
+        // what serializing the merged asset would write, with a new default field
+        let written = "E(h: H(max: 2, current: 1), s: \"c\", m: {\"x\": 1, \"y\": 2}, d: 0)";
+        let child = chain.unmerge(written).unwrap();
+        assert_eq!(
+            child,
+            "E(extends: \"../b.enemy\", h: H(max: 2), s: \"c\", d: 0)"
+        );
+        assert!(chain.preserved_by(&child).unwrap());
+        assert!(chain.preserved_by(files[0].1).unwrap());
+        // maps are unmerged key by key
+        assert_eq!(
+            chain
+                .unmerge("E(h: H(max: 1, current: 1), m: {\"x\": 1, \"y\": 3})")
+                .unwrap(),
+            "E(extends: \"../b.enemy\", m: {\"y\": 3})"
+        );
+
+        // inlining the template loses the extends
+        assert!(!chain.preserved_by(written).unwrap());
+        // and changing a value loses the old one
+        assert!(!chain
+            .preserved_by("E(extends: \"../b.enemy\", h: H(max: 3), s: \"c\")")
+            .unwrap());
+    }
+
+    #[test]
+    fn checks_what_is_preserved() {
+        assert!(preserves("E(b: 1.0, a: \"x\")", "E(a: \"x\", b: 1, c: [])").unwrap());
+        assert!(!preserves("E(a: 1, b: 2)", "E(a: 1)").unwrap());
+        assert!(!preserves("E(a: [1, 2])", "E(a: [1])").unwrap());
+        assert!(!preserves("E(a: A(x: 1))", "E(a: B(x: 1))").unwrap());
+    }
+
+    #[test]
+    fn spans_follow_fields_keys_and_indices() {
+        let text = "E(a: [1, (x: 2)], m: {\"k\": 3})";
+        let spans = value_spans(text).unwrap();
+        let span = |path: &[&str]| {
+            let (_, span) = spans.iter().find(|(p, _)| p[..] == path[..]).unwrap();
+            &text[span.clone()]
+        };
+        assert_eq!(span(&[]), text);
+        assert_eq!(span(&["a"]), "[1, (x: 2)]");
+        assert_eq!(span(&["a", "1", "x"]), "2");
+        assert_eq!(span(&["m", "k"]), "3");
+    }
+}
@@ -0,0 +1,115 @@
+// helpers for tests that need assets without touching the disk.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use bevy::{
+    asset::{Asset, AssetIo, AssetIoError, FileType, LoadState, Metadata},
+    prelude::*,
+    utils::BoxedFuture,
+};
+
+/// `(to_watch, to_reload)`, as passed to `watch_path_for_changes`
+pub type Watch = (PathBuf, Option<PathBuf>);
+
+/// An asset folder that only exists in memory
+#[derive(Clone, Default)]
+pub struct MemoryAssetIo {
+    files: Arc<HashMap<PathBuf, Vec<u8>>>,
+    /// every path that was watched for changes
+    pub watched: Arc<Mutex<Vec<Watch>>>,
+}
+
+impl MemoryAssetIo {
+    pub fn new(files: &[(&str, &str)]) -> Self {
+        Self {
+            files: Arc::new(
+                files
+                    .iter()
+                    .map(|(path, text)| (PathBuf::from(path), text.as_bytes().to_vec()))
+                    .collect(),
+            ),
+            watched: default(),
+        }
+    }
+}
+
+impl AssetIo for MemoryAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        Box::pin(async move {
+            self.files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| AssetIoError::NotFound(path.to_path_buf()))
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        let path = path.to_path_buf();
+        let entries: Vec<_> = self
+            .files
+            .keys()
+            .filter(|file| file.parent() == Some(&path))
+            .cloned()
+            .collect();
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn get_metadata(&self, path: &Path) -> Result<Metadata, AssetIoError> {
+        if self.files.contains_key(path) {
+            Ok(Metadata::new(FileType::File))
+        } else if self.files.keys().any(|file| file.starts_with(path)) {
+            Ok(Metadata::new(FileType::Directory))
+        } else {
+            Err(AssetIoError::NotFound(path.to_path_buf()))
+        }
+    }
+
+    fn watch_path_for_changes(
+        &self,
+        to_watch: &Path,
+        to_reload: Option<PathBuf>,
+    ) -> Result<(), AssetIoError> {
+        self.watched
+            .lock()
+            .unwrap()
+            .push((to_watch.to_path_buf(), to_reload));
+        Ok(())
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        Ok(())
+    }
+}
+
+/// A headless app whose asset server reads from `io`
+pub fn asset_app(io: MemoryAssetIo) -> App {
+    let mut app = App::new();
+    // the asset plugin keeps an asset server that is already there
+    app.insert_resource(AssetServer::new(io))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin::default());
+    app
+}
+
+/// Load a file and update the app until it is done loading
+pub fn load<T: Asset>(app: &mut App, path: &str) -> (Handle<T>, LoadState) {
+    let handle: Handle<T> = app.world.resource::<AssetServer>().load(path);
+    for _ in 0..200 {
+        app.update();
+        let asset_server = app.world.resource::<AssetServer>();
+        match asset_server.get_load_state(&handle) {
+            state @ (LoadState::Loaded | LoadState::Failed) => return (handle, state),
+            _ => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+    panic!("\"{path}\" never finished loading");
+}
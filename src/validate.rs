@@ -0,0 +1,46 @@
+// checks on content definitions that deserializing them can't catch on its own,
+// used by the btll-lint binary
+
+/// A problem found in a content definition
+#[derive(Debug)]
+pub struct Problem {
+    pub message: String,
+    /// text in the file the problem is about, used to point at the right line
+    pub needle: String,
+    /// path of field names, map keys and list indices to the value the needle is in,
+    /// empty to search the whole file
+    pub field: Vec<String>,
+}
+
+impl Problem {
+    pub fn new(message: impl Into<String>, needle: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            needle: needle.into(),
+            field: Vec::new(),
+        }
+    }
+
+    /// Point the problem into the value in `field`, see [`in_field`]
+    pub fn at(mut self, field: impl Into<String>) -> Self {
+        self.field.push(field.into());
+        self
+    }
+}
+
+pub trait Validate {
+    fn validate(&self, _problems: &mut Vec<Problem>) {}
+}
+
+/// Run a validation of the value in `field`, so its problems point into that value
+pub fn in_field(
+    field: &str,
+    problems: &mut Vec<Problem>,
+    validate: impl FnOnce(&mut Vec<Problem>),
+) {
+    let start = problems.len();
+    validate(problems);
+    for problem in &mut problems[start..] {
+        problem.field.insert(0, field.to_string());
+    }
+}